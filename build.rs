@@ -7,9 +7,17 @@ use std::path::Path;
 
 fn main() {
     let dest = env::var("OUT_DIR").unwrap();
-    let mut file = File::create(&Path::new(&dest).join("bindings.rs")).unwrap();
+    let mut file = File::create(Path::new(&dest).join("bindings.rs")).unwrap();
 
-    Registry::new(Api::Gl, (3, 3), Profile::Core, Fallbacks::All, [])
+    // ARB_buffer_storage (core in 4.4) for persistently mapped buffers where available, and
+    // ARB_ES3_compatibility for the fixed primitive restart index GLES uses
+    Registry::new(
+        Api::Gl,
+        (3, 3),
+        Profile::Core,
+        Fallbacks::All,
+        ["GL_ARB_buffer_storage", "GL_ARB_ES3_compatibility"],
+    )
         .write_bindings(GlobalGenerator, &mut file)
         .unwrap();
 }
@@ -0,0 +1,132 @@
+//
+// Derive macros for procedural_generation. `#[derive(VertexLayout)]` describes the fields of a
+// `#[repr(C)]` vertex struct as vertex attributes, see backend::VertexLayout
+//
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt};
+
+//
+// Every field becomes an attribute at the location of its position in the struct, unless
+// given one with `#[vertex(location = N)]`. Integer fields are converted to floats as they
+// are, `#[vertex(normalized)]` maps them to 0..1 (or -1..1 when signed) and
+// `#[vertex(integer)]` keeps them integers for `ivec`/`uvec` shader inputs
+//
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "VertexLayout cannot be derived for generic structs"));
+    }
+
+    let mut repr_c = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            repr_c |= meta.path.is_ident("C");
+            Ok(())
+        })?;
+    }
+
+    if !repr_c {
+        return Err(syn::Error::new_spanned(name, "VertexLayout needs #[repr(C)] so the field offsets are fixed"));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(name, "VertexLayout needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "VertexLayout can only be derived for structs")),
+    };
+
+    let mut attributes = Vec::new();
+    let mut sizes = Vec::new();
+    let mut checks = Vec::new();
+    let mut locations = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let mut location = index as u32;
+        let mut normalized = false;
+        let mut integer = false;
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("vertex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    location = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("normalized") {
+                    normalized = true;
+                } else if meta.path.is_ident("integer") {
+                    integer = true;
+                } else {
+                    return Err(meta.error("expected `location = N`, `normalized` or `integer`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        if normalized && integer {
+            return Err(syn::Error::new_spanned(field, "an attribute cannot be both `normalized` and `integer`"));
+        }
+
+        if locations.contains(&location) {
+            return Err(syn::Error::new_spanned(field, format!("vertex location {} is used twice", location)));
+        }
+        locations.push(location);
+
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+
+        attributes.push(quote! {
+            ::procedural_generation::backend::VertexAttribute::of::<#ty>(
+                #location,
+                ::core::mem::offset_of!(#name, #ident),
+                #normalized,
+                #integer,
+            )
+        });
+        sizes.push(quote! { ::core::mem::size_of::<#ty>() });
+
+        let field = quote! { <#ty as ::procedural_generation::backend::VertexField> };
+        checks.push(quote! {
+            const _: () = assert!(
+                #field::COMPONENTS >= 1 && #field::COMPONENTS <= 4,
+                "vertex attributes have 1 to 4 components"
+            );
+        });
+        if normalized || integer {
+            checks.push(quote! {
+                const _: () = assert!(
+                    !#field::KIND.is_float(),
+                    "float vertex attributes cannot be normalized or integer"
+                );
+            });
+        }
+    }
+
+    Ok(quote! {
+        // The bytes of every vertex are uploaded as they are, padding would be uninitialised
+        const _: () = assert!(
+            ::core::mem::size_of::<#name>() == 0 #(+ #sizes)*,
+            "VertexLayout structs must not contain padding"
+        );
+
+        #(#checks)*
+
+        unsafe impl ::procedural_generation::backend::VertexLayout for #name {
+            fn attributes() -> ::std::vec::Vec<::procedural_generation::backend::VertexAttribute> {
+                ::std::vec![#(#attributes),*]
+            }
+        }
+    })
+}
@@ -0,0 +1,13 @@
+// Per-frame camera and lighting data, uploaded once a frame from FrameUniforms
+
+layout (std140) uniform Frame
+{
+    mat4 view;
+    mat4 projection;
+    vec3 camera_position;
+    float time;
+    vec3 sun_direction;
+    float fog_density;
+    vec3 sun_color;
+    vec3 fog_color;
+};
@@ -8,8 +8,8 @@ out vec3 fragment_pos;
 out vec3 surface_normal;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "frame.glsl"
 
 void main()
 {
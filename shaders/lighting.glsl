@@ -0,0 +1,20 @@
+// Lighting shared by the fragment shaders, adapted from the following tutorials:
+// https://learnopengl.com/Lighting/Basic-Lighting
+
+vec3 ambient_light(vec3 light_color, float strength)
+{
+    return strength * light_color;
+}
+
+vec3 diffuse_light(vec3 light_color, vec3 light_dir, vec3 norm)
+{
+    float diff = max(dot(norm, light_dir), 0.0);
+    return diff * light_color;
+}
+
+vec3 specular_light(vec3 light_color, vec3 light_dir, vec3 view_dir, vec3 norm, float strength)
+{
+    vec3 reflect_dir = reflect(-light_dir, norm);
+    float spec = pow(max(dot(view_dir, reflect_dir), 0.0), 32.0);
+    return strength * spec * light_color;
+}
@@ -4,8 +4,8 @@
 layout (location = 0) in vec3 a_pos;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "frame.glsl"
 
 void main()
 {
@@ -8,23 +8,40 @@ in vec3 fragment_pos;
 in vec3 surface_normal;
 in vec2 tex_coord;
 
-uniform vec3 light_color;
-
-uniform vec3 light_pos;
 uniform sampler2D t_texture;
 
-void main()
+#include "frame.glsl"
+#include "lighting.glsl"
+
+#ifdef LOD
+in float morph;
+
+// Colours each patch by its level, blending into the next level's colour as it morphs
+uniform bool lod_debug;
+uniform int lod_level;
+
+vec3 lod_color(float level)
 {
-    // Ambient lighting
-    float ambient_strength = 0.5;
-    vec3 ambient = ambient_strength * light_color;
+    return 0.5 + 0.5 * cos(6.28318 * (level / 6.0 + vec3(0.0, 0.33, 0.67)));
+}
+#endif
 
-    // diffuse 
+void main()
+{
     vec3 norm = normalize(surface_normal);
-    vec3 light_dir = normalize(light_pos - fragment_pos);
-    float diff = max(dot(norm, light_dir), 0.0);
-    vec3 diffuse = diff * light_color;
-        
+
+    vec3 ambient = ambient_light(sun_color, 0.5);
+    vec3 diffuse = diffuse_light(sun_color, sun_direction, norm);
+
     vec3 result = (ambient + diffuse) * texture(t_texture, tex_coord).xyz;
-    color = vec4(result, 1.0f);
-}
\ No newline at end of file
+
+    // Exponential fog over the distance to the camera
+    float fog = 1.0 - exp(-fog_density * length(camera_position - fragment_pos));
+    color = vec4(mix(result, fog_color, fog), 1.0f);
+
+#ifdef LOD
+    if (lod_debug) {
+        color = vec4(lod_color(float(lod_level) + morph), 1.0);
+    }
+#endif
+}
@@ -10,14 +10,34 @@ out vec3 surface_normal;
 out vec2 tex_coord;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "frame.glsl"
+
+#ifdef LOD
+// Height of the vertex on the next coarser level, see generation::lod
+layout (location = 3) in float a_morph_height;
+
+// Distances from the camera over which the patch morphs into the next coarser level
+uniform vec2 lod_morph;
+
+out float morph;
+#endif
 
 void main()
 {
-    fragment_pos = vec3(model * vec4(a_pos, 1.0));
+    vec3 position = a_pos;
+
+#ifdef LOD
+    // Distance to the vertex flattened onto y = 0, as the patches were selected with
+    vec3 world = vec3(model * vec4(a_pos, 1.0));
+    float camera_distance = length(vec3(world.x - camera_position.x, camera_position.y, world.z - camera_position.z));
+    morph = clamp((camera_distance - lod_morph.x) / (lod_morph.y - lod_morph.x), 0.0, 1.0);
+    position.y = mix(a_pos.y, a_morph_height, morph);
+#endif
+
+    fragment_pos = vec3(model * vec4(position, 1.0));
     surface_normal = mat3(transpose(inverse(model))) * a_normal;
     tex_coord = a_tex_coord;
 
-    gl_Position = projection * view * model * vec4(a_pos, 1.0f);
+    gl_Position = projection * view * model * vec4(position, 1.0f);
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ use vek::mat::Mat4;
 use vek::vec::Vec3;
 
 // Defines several possible options for camera movement. Used as abstraction to stay away from window-system specific input methods
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CameraMovement {
     FORWARD,
     BACKWARD,
@@ -111,11 +113,7 @@ impl Camera {
         }
         self.yaw += yaw_offset;
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
-        } else if self.pitch < -89.0 {
-            self.pitch = -89.0;
-        }
+        self.pitch = self.pitch.clamp(-89.0, 89.0);
 
         self.update_vectors();
     }
@@ -0,0 +1,254 @@
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::backend::error::ShaderStage;
+use crate::backend::gl_gen::gl;
+
+//
+// GLSL dialect understood by the context that was created. The shaders are written against
+// GLSL 3.30 core and rewritten on load for the fallback contexts, see
+// PreprocessedSource::translate
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlslVersion {
+    // OpenGL 3.3 and later, the shaders are used as written
+    Glsl330,
+    // OpenGL ES 3.0 and later
+    GlslEs300,
+    // OpenGL 2.1, also used for 3.0 to 3.2 which do not accept GLSL 3.30
+    Glsl120,
+}
+
+// Dialect of the current context, set by Renderer::new
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+// mat4 inverse() only exists from GLSL 1.40, this is the cofactor expansion gl-matrix uses
+const INVERSE_POLYFILL: &str = "\
+mat4 inverse(mat4 m)
+{
+    float a00 = m[0][0], a01 = m[0][1], a02 = m[0][2], a03 = m[0][3];
+    float a10 = m[1][0], a11 = m[1][1], a12 = m[1][2], a13 = m[1][3];
+    float a20 = m[2][0], a21 = m[2][1], a22 = m[2][2], a23 = m[2][3];
+    float a30 = m[3][0], a31 = m[3][1], a32 = m[3][2], a33 = m[3][3];
+    float b00 = a00 * a11 - a01 * a10, b01 = a00 * a12 - a02 * a10, b02 = a00 * a13 - a03 * a10;
+    float b03 = a01 * a12 - a02 * a11, b04 = a01 * a13 - a03 * a11, b05 = a02 * a13 - a03 * a12;
+    float b06 = a20 * a31 - a21 * a30, b07 = a20 * a32 - a22 * a30, b08 = a20 * a33 - a23 * a30;
+    float b09 = a21 * a32 - a22 * a31, b10 = a21 * a33 - a23 * a31, b11 = a22 * a33 - a23 * a32;
+    float det = b00 * b11 - b01 * b10 + b02 * b09 + b03 * b08 - b04 * b07 + b05 * b06;
+    return mat4(
+        a11 * b11 - a12 * b10 + a13 * b09, a02 * b10 - a01 * b11 - a03 * b09,
+        a31 * b05 - a32 * b04 + a33 * b03, a22 * b04 - a21 * b05 - a23 * b03,
+        a12 * b08 - a10 * b11 - a13 * b07, a00 * b11 - a02 * b08 + a03 * b07,
+        a32 * b02 - a30 * b05 - a33 * b01, a20 * b05 - a22 * b02 + a23 * b01,
+        a10 * b10 - a11 * b08 + a13 * b06, a01 * b08 - a00 * b10 - a03 * b06,
+        a30 * b04 - a31 * b02 + a33 * b00, a21 * b02 - a20 * b04 - a23 * b00,
+        a11 * b07 - a10 * b09 - a12 * b06, a00 * b09 - a01 * b07 + a02 * b06,
+        a31 * b01 - a30 * b03 - a32 * b00, a20 * b03 - a21 * b01 + a22 * b00) / det;
+}";
+
+impl GlslVersion {
+    //
+    // Picks the dialect from a GL_VERSION string, e.g. "4.5 (Core Profile) Mesa 23.2.1" or
+    // "OpenGL ES 3.2 Mesa 23.2.1"
+    //
+    pub fn from_gl_version(version: &str) -> Self {
+        let (es, number) = match version.strip_prefix("OpenGL ES") {
+            Some(rest) => (true, rest.trim_start_matches(|c: char| !c.is_ascii_digit())),
+            None => (false, version),
+        };
+
+        let mut parts = number.split(|c: char| !c.is_ascii_digit());
+        let major: u32 = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+        let minor: u32 = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+
+        match (es, (major, minor) >= (3, 3)) {
+            (true, _) => GlslVersion::GlslEs300,
+            (false, true) => GlslVersion::Glsl330,
+            (false, false) => GlslVersion::Glsl120,
+        }
+    }
+
+    // Asks the current context, GL must be loaded
+    pub fn detect() -> Self {
+        let version = unsafe { gl::GetString(gl::VERSION) };
+
+        if version.is_null() {
+            return GlslVersion::Glsl330;
+        }
+
+        let version = unsafe { CStr::from_ptr(version as *const _) };
+        Self::from_gl_version(&version.to_string_lossy())
+    }
+
+    // Dialect shaders are compiled for, GLSL 3.30 until a Renderer is created
+    pub fn current() -> Self {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => GlslVersion::GlslEs300,
+            2 => GlslVersion::Glsl120,
+            _ => GlslVersion::Glsl330,
+        }
+    }
+
+    pub fn set_current(version: GlslVersion) {
+        CURRENT.store(version as u8, Ordering::Relaxed);
+    }
+
+    // GL 2.1 has no uniform buffers, GLSL 1.20 no uniform blocks
+    pub fn supports_uniform_buffers(self) -> bool {
+        self != GlslVersion::Glsl120
+    }
+
+    // Restart strips need GL 3.1 or GLES 3.0
+    pub fn supports_primitive_restart(self) -> bool {
+        self != GlslVersion::Glsl120
+    }
+
+    pub fn header(self) -> &'static str {
+        match self {
+            GlslVersion::Glsl330 => "#version 330 core",
+            GlslVersion::GlslEs300 => "#version 300 es",
+            GlslVersion::Glsl120 => "#version 120",
+        }
+    }
+
+    // Lines that go right after the #version line of a shader
+    pub(crate) fn prelude(self, stage: ShaderStage, source: &str) -> Vec<&'static str> {
+        match (self, stage) {
+            // Fragment shaders have no default float precision in GLSL ES
+            (GlslVersion::GlslEs300, ShaderStage::Fragment) => vec!["precision highp float;"],
+            (GlslVersion::Glsl120, _) if Self::find_call(source, "inverse").is_some() => {
+                INVERSE_POLYFILL.lines().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    //
+    // Rewrites a GLSL 3.30 line for this dialect. The result is always a single line so line
+    // numbers stay put. For GLSL 1.20 vertex inputs become attributes, with the location they
+    // had returned so it can be bound before linking, the other inputs and outputs become
+    // varyings, the fragment output becomes gl_FragColor and texture() becomes texture2D().
+    // Uniform blocks without an instance name become plain uniforms, `in_block` tracks
+    // whether the line is inside one
+    //
+    pub(crate) fn translate_line(
+        self,
+        line: &str,
+        stage: ShaderStage,
+        in_block: &mut bool,
+    ) -> (String, Option<(u32, String)>) {
+        let body = line.trim_start();
+        let indent = &line[..line.len() - body.len()];
+
+        if body.starts_with("#version") {
+            return (self.header().to_string(), None);
+        }
+
+        if self != GlslVersion::Glsl120 {
+            return (line.to_string(), None);
+        }
+
+        if *in_block {
+            return match body {
+                _ if body.starts_with('}') => {
+                    *in_block = false;
+                    (String::new(), None)
+                }
+                _ if body.starts_with('{') || body.is_empty() || body.starts_with("//") => {
+                    (format!("{}{}", indent, body.trim_start_matches('{')), None)
+                }
+                _ => (format!("{}uniform {}", indent, body), None),
+            };
+        }
+
+        let (location, body) = match Self::strip_layout(body) {
+            Some((location, rest)) => (location, rest),
+            None => (None, body),
+        };
+
+        // A block header, `uniform Frame` with the brace on the same or the next line
+        if Self::strip_keyword(body, "uniform").is_some_and(|declaration| !declaration.contains(';')) {
+            *in_block = true;
+            return (String::new(), None);
+        }
+
+        let mut attribute = None;
+        let body = match (stage, Self::strip_keyword(body, "in"), Self::strip_keyword(body, "out")) {
+            (ShaderStage::Vertex, Some(declaration), _) => {
+                attribute = location.zip(Self::declared_name(declaration));
+                format!("attribute {}", declaration)
+            }
+            (ShaderStage::Vertex, _, Some(declaration)) | (ShaderStage::Fragment, Some(declaration), _) => {
+                format!("varying {}", declaration)
+            }
+            (ShaderStage::Fragment, _, Some(declaration)) => match Self::declared_name(declaration) {
+                Some(name) => format!("#define {} gl_FragColor", name),
+                None => body.to_string(),
+            },
+            _ => body.to_string(),
+        };
+
+        (format!("{}{}", indent, Self::rename_calls(&body, "texture", "texture2D")), attribute)
+    }
+
+    // Splits `layout (location = N)` off a declaration
+    fn strip_layout(body: &str) -> Option<(Option<u32>, &str)> {
+        let rest = Self::strip_word(body, "layout")?.trim_start().strip_prefix('(')?;
+        let (qualifiers, rest) = rest.split_at(rest.find(')')?);
+
+        let location = qualifiers.split(',').find_map(|qualifier| {
+            let (name, value) = qualifier.split_once('=')?;
+            if name.trim() == "location" {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        });
+
+        Some((location, rest[1..].trim_start()))
+    }
+
+    // Declaration following a storage qualifier, e.g. `vec3 a_pos;` for `in vec3 a_pos;`
+    fn strip_keyword<'a>(body: &'a str, keyword: &str) -> Option<&'a str> {
+        let rest = Self::strip_word(body, keyword)?;
+        rest.starts_with(char::is_whitespace).then(|| rest.trim_start())
+    }
+
+    fn strip_word<'a>(body: &'a str, word: &str) -> Option<&'a str> {
+        let rest = body.strip_prefix(word)?;
+        (!rest.starts_with(Self::is_identifier)).then_some(rest)
+    }
+
+    fn declared_name(declaration: &str) -> Option<String> {
+        let declaration = declaration.split(';').next()?;
+        let name = declaration.split_whitespace().last()?;
+        Some(name.split('[').next()?.to_string())
+    }
+
+    fn is_identifier(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    // Byte offset of the first call to `function`, not counting identifiers that end with it
+    fn find_call(source: &str, function: &str) -> Option<usize> {
+        source.match_indices(function).map(|(start, _)| start).find(|&start| {
+            let before = source[..start].chars().next_back();
+            let after = source[start + function.len()..].trim_start();
+            !before.is_some_and(Self::is_identifier) && after.starts_with('(')
+        })
+    }
+
+    fn rename_calls(line: &str, from: &str, to: &str) -> String {
+        let mut renamed = String::new();
+        let mut rest = line;
+
+        while let Some(start) = Self::find_call(rest, from) {
+            renamed.push_str(&rest[..start]);
+            renamed.push_str(to);
+            rest = &rest[start + from.len()..];
+        }
+
+        renamed.push_str(rest);
+        renamed
+    }
+}
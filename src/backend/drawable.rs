@@ -1,9 +1,13 @@
 use std::mem;
+use std::slice;
 
 use bitflags::bitflags;
 
+use crate::backend::error::{check_gl_error, Error, Result};
 use crate::backend::gl_gen::gl;
 use crate::backend::gl_gen::gl::types::*;
+use crate::backend::vertex_layout::{as_bytes, VertexAttribute, VertexLayout, Vertices};
+use crate::mesh::aabb::Aabb;
 
 bitflags! {
     pub struct DrawType: u32 {
@@ -24,12 +28,10 @@ bitflags! {
 #[allow(dead_code)]
 pub enum Buffer {
     ArrayBuffer {
-        vertex_attributes: Vec<f32>,
-        vertex_attribute_pointers: Vec<VertexAttribPointer>,
+        vertices: Vertices,
     },
     IndexBuffer {
-        vertex_attributes: Vec<f32>,
-        vertex_attribute_pointers: Vec<VertexAttribPointer>,
+        vertices: Vertices,
         indices: Vec<u32>,
     },
 }
@@ -37,6 +39,8 @@ pub enum Buffer {
 pub struct VertexArrayObject(pub GLuint, Vec<Box<dyn GlBuffer>>);
 
 impl VertexArrayObject {
+    // Needs a current GL context, so there is no Default
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let mut vao = 0;
 
@@ -68,17 +72,106 @@ impl Drop for VertexArrayObject {
 
 pub trait GlBuffer {}
 
+//
+// How often the contents of a buffer are expected to change: STATIC for data uploaded once,
+// DYNAMIC for data updated now and then, e.g. terrain being edited, and STREAM for data
+// rewritten every frame
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum BufferHint {
     STATIC,
-    _DYNAMIC,
-    _STREAM,
+    DYNAMIC,
+    STREAM,
 }
 
-pub struct ArrayBuffer(GLuint);
+impl BufferHint {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            BufferHint::STATIC => gl::STATIC_DRAW,
+            BufferHint::DYNAMIC => gl::DYNAMIC_DRAW,
+            BufferHint::STREAM => gl::STREAM_DRAW,
+        }
+    }
+}
+
+// Points the attributes of a vertex layout at the buffer bound to GL_ARRAY_BUFFER, with the
+// vertex array they belong to bound
+pub(crate) fn point_attributes(stride: usize, attributes: &[VertexAttribute]) {
+    for attribute in attributes {
+        unsafe {
+            // Integer attributes need the I variant, the other one converts to float
+            if attribute.integer {
+                gl::VertexAttribIPointer(
+                    attribute.location,
+                    attribute.components as _,
+                    attribute.kind.gl_enum(),
+                    stride as _,
+                    attribute.offset as _,
+                );
+            } else {
+                gl::VertexAttribPointer(
+                    attribute.location,
+                    attribute.components as _,
+                    attribute.kind.gl_enum(),
+                    attribute.normalized as _,
+                    stride as _,
+                    attribute.offset as _,
+                );
+            }
+            gl::EnableVertexAttribArray(attribute.location);
+        }
+    }
+}
+
+//
+// Writes `data` at byte `offset` of the buffer bound to `target`, which holds `size` bytes.
+// Rewriting all of it orphans the old storage instead, so the driver can hand out fresh
+// memory rather than stall until draws still reading the old contents are done
+//
+fn write_range(target: GLenum, size: usize, offset: usize, data: &[u8], hint: BufferHint) -> Result<()> {
+    if offset.checked_add(data.len()).is_none_or(|end| end > size) {
+        return Err(Error::BufferRange {
+            offset,
+            length: data.len(),
+            size,
+        });
+    }
+
+    unsafe {
+        if offset == 0 && data.len() == size {
+            gl::BufferData(target, size as _, data.as_ptr() as _, hint.gl_enum());
+        } else if !data.is_empty() {
+            gl::BufferSubData(target, offset as _, data.len() as _, data.as_ptr() as _);
+        }
+    }
+
+    check_gl_error()
+}
+
+// Detaches the storage of the buffer bound to `target` without filling the new one
+fn orphan(target: GLenum, size: usize, hint: BufferHint) {
+    unsafe {
+        gl::BufferData(target, size as _, std::ptr::null(), hint.gl_enum());
+    }
+}
+
+//
+// Vertex buffer. Filled with bind_data, which also sets up the attributes, and afterwards
+// updated in place with update, or respecified with bind_data again
+//
+pub struct ArrayBuffer {
+    id: GLuint,
+    // Bytes allocated by the last bind_data
+    size: usize,
+    hint: BufferHint,
+}
 
 impl GlBuffer for ArrayBuffer {}
 
 impl ArrayBuffer {
+    // Needs a current GL context, so there is no Default
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let mut vbo = 0;
 
@@ -86,63 +179,102 @@ impl ArrayBuffer {
             gl::GenBuffers(1, &mut vbo);
         }
 
-        ArrayBuffer(vbo)
+        ArrayBuffer {
+            id: vbo,
+            size: 0,
+            hint: BufferHint::STATIC,
+        }
     }
 
-    pub fn bind_data(
-        &mut self,
-        vertex_attributes: Vec<f32>,
-        vertex_attribute_pointers: Vec<VertexAttribPointer>,
-        hint: BufferHint,
-    ) {
-        let data = &vertex_attributes;
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
 
-        let hint = match hint {
-            BufferHint::_DYNAMIC => gl::DYNAMIC_DRAW,
-            BufferHint::STATIC => gl::STATIC_DRAW,
-            BufferHint::_STREAM => gl::STREAM_DRAW,
-        };
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Uploads the vertices and points the attributes of their layout at them, with the
+    // vertex array they belong to bound. Calling it again reallocates the buffer, which may
+    // change its size
+    pub fn bind_data<T: VertexLayout>(&mut self, vertices: &[T], hint: BufferHint) {
+        self.upload(as_bytes(vertices), T::stride(), &T::attributes(), hint);
+    }
+
+    pub fn bind_vertices(&mut self, vertices: &Vertices, hint: BufferHint) {
+        self.upload(vertices.data(), vertices.stride(), vertices.attributes(), hint);
+    }
+
+    fn upload(&mut self, data: &[u8], stride: usize, attributes: &[VertexAttribute], hint: BufferHint) {
+        self.size = data.len();
+        self.hint = hint;
 
         unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.0);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (data.len() * mem::size_of_val(&data[0])) as _,
-                data.as_ptr() as _,
-                hint,
-            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+            gl::BufferData(gl::ARRAY_BUFFER, data.len() as _, data.as_ptr() as _, hint.gl_enum());
 
-            for ptr in &vertex_attribute_pointers {
-                gl::VertexAttribPointer(
-                    ptr.index,
-                    ptr.size as _,
-                    gl::FLOAT,
-                    0,
-                    ptr.stride as _,
-                    ptr.offset as _,
-                );
-                gl::EnableVertexAttribArray(ptr.index);
-            }
+            point_attributes(stride, attributes);
 
             // Unbind vertex array
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
+
+    // Overwrites the vertices from index `first` on, keeping the rest of the buffer
+    pub fn update<T: VertexLayout>(&mut self, first: usize, vertices: &[T]) -> Result<()> {
+        self.update_bytes(first * T::stride(), as_bytes(vertices))
+    }
+
+    // Overwrites the bytes from `offset` on, the vertices must have the layout the buffer
+    // was filled with
+    pub fn update_bytes(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+        }
+
+        let result = write_range(gl::ARRAY_BUFFER, self.size, offset, data, self.hint);
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        result
+    }
+
+    // Gives the buffer fresh, undefined storage of the same size, for when every vertex is
+    // about to be rewritten through update
+    pub fn orphan(&mut self) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+            orphan(gl::ARRAY_BUFFER, self.size, self.hint);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
 }
 
 impl Drop for ArrayBuffer {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteBuffers(1, &self.0);
+            gl::DeleteBuffers(1, &self.id);
         }
     }
 }
 
-pub struct ElementBuffer(GLuint);
+//
+// Index buffer, updated the same way as ArrayBuffer. It stays bound to the vertex array it
+// was filled with, so that vertex array must be bound to update it
+//
+pub struct ElementBuffer {
+    id: GLuint,
+    size: usize,
+    hint: BufferHint,
+}
 
 impl GlBuffer for ElementBuffer {}
 
 impl ElementBuffer {
+    // Needs a current GL context, so there is no Default
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let mut vbo = 0;
 
@@ -150,34 +282,67 @@ impl ElementBuffer {
             gl::GenBuffers(1, &mut vbo);
         }
 
-        ElementBuffer(vbo)
+        ElementBuffer {
+            id: vbo,
+            size: 0,
+            hint: BufferHint::STATIC,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    // Number of indices allocated
+    pub fn len(&self) -> usize {
+        self.size / mem::size_of::<u32>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
     }
 
     pub fn bind_data(&mut self, indices: Vec<u32>, hint: BufferHint) {
         let data = &indices;
 
-        let hint = match hint {
-            BufferHint::_DYNAMIC => gl::DYNAMIC_DRAW,
-            BufferHint::STATIC => gl::STATIC_DRAW,
-            BufferHint::_STREAM => gl::STREAM_DRAW,
-        };
+        self.size = mem::size_of_val(data.as_slice());
+        self.hint = hint;
 
         unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
-                (data.len() * mem::size_of_val(&data[0])) as _,
+                self.size as _,
                 data.as_ptr() as _,
-                hint,
+                hint.gl_enum(),
             );
         }
     }
+
+    // Overwrites the indices from position `first` on
+    pub fn update(&mut self, first: usize, indices: &[u32]) -> Result<()> {
+        let data = unsafe { slice::from_raw_parts(indices.as_ptr() as *const u8, mem::size_of_val(indices)) };
+
+        unsafe {
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
+        }
+
+        write_range(gl::ELEMENT_ARRAY_BUFFER, self.size, first * mem::size_of::<u32>(), data, self.hint)
+    }
+
+    pub fn orphan(&mut self) {
+        unsafe {
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
+        }
+
+        orphan(gl::ELEMENT_ARRAY_BUFFER, self.size, self.hint);
+    }
 }
 
 impl Drop for ElementBuffer {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteBuffers(1, &self.0);
+            gl::DeleteBuffers(1, &self.id);
         }
     }
 }
@@ -186,15 +351,11 @@ pub struct DrawableAttributes {
     pub buffer: Buffer,
     pub draw_count: usize,
     pub draw_primitive: DrawPrimitive,
+    // Box around the vertices, before the object's transform. Objects without one are never
+    // culled
+    pub bounds: Option<Aabb>,
 }
 
 pub trait Drawable {
     fn vertex_attributes(&self) -> DrawableAttributes;
 }
-
-pub struct VertexAttribPointer {
-    pub index: u32,
-    pub size: usize,
-    pub stride: usize,
-    pub offset: usize,
-}
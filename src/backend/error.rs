@@ -0,0 +1,129 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::backend::gl_gen::gl;
+use crate::backend::gl_gen::gl::types::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+        })
+    }
+}
+
+//
+// Everything that can go wrong creating or using GL resources. Failures are returned rather
+// than panicking so tools can report them and carry on, e.g. keep drawing with the last
+// shader that compiled
+//
+#[derive(Debug)]
+pub enum Error {
+    // A shader or texture file could not be read
+    Io { path: PathBuf, source: io::Error },
+    // A texture file could be read but not decoded
+    Image { path: PathBuf, source: image::ImageError },
+    // A shader has a malformed or recursive #include
+    Preprocess { path: PathBuf, line: u32, message: String },
+    // A shader stage failed to compile, `log` is the driver's info log
+    Compile { stage: ShaderStage, log: String },
+    // The compiled stages failed to link into a program
+    Link { log: String },
+    // glGetError reported an error
+    Gl { code: GLenum },
+    // A buffer update wrote past the end of the buffer
+    BufferRange { offset: usize, length: usize, size: usize },
+    // Vertices given to update a buffer have a different layout than the ones it holds
+    VertexLayoutMismatch,
+    // The context or surface failed, e.g. swapping buffers
+    Context(glutin::error::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // Name of a glGetError code
+    fn gl_name(code: GLenum) -> &'static str {
+        match code {
+            gl::INVALID_ENUM => "GL_INVALID_ENUM",
+            gl::INVALID_VALUE => "GL_INVALID_VALUE",
+            gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+            gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+            gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+            _ => "unknown error",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "Unable to read {}: {}", path.display(), source),
+            Error::Image { path, source } => write!(f, "Unable to decode {}: {}", path.display(), source),
+            Error::Preprocess { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Compile { stage, log } => write!(f, "Unable to compile the {} shader:\n{}", stage, log),
+            Error::Link { log } => write!(f, "Unable to link the shader program:\n{}", log),
+            Error::Gl { code } => write!(f, "OpenGL error {} (0x{:04X})", Self::gl_name(*code), code),
+            Error::BufferRange { offset, length, size } => write!(
+                f,
+                "Unable to write {} bytes at offset {} into a buffer of {} bytes",
+                length, offset, size
+            ),
+            Error::VertexLayoutMismatch => f.write_str("The vertices do not have the layout of the buffer they update"),
+            Error::Context(e) => write!(f, "OpenGL context error: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Context(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<glutin::error::Error> for Error {
+    fn from(e: glutin::error::Error) -> Self {
+        Error::Context(e)
+    }
+}
+
+// Returns the oldest error recorded by GL, clearing every pending one
+pub fn check_gl_error() -> Result<()> {
+    let mut first = None;
+
+    loop {
+        let code = unsafe { gl::GetError() };
+        if code == gl::NO_ERROR {
+            break;
+        }
+        first.get_or_insert(code);
+    }
+
+    match first {
+        Some(code) => Err(Error::Gl { code }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,53 @@
+use vek::mat::Mat4;
+use vek::vec::Vec3;
+
+use crate::backend::uniform_buffer::{BlockVisitor, UniformBlock};
+
+//
+// Per-frame camera and lighting data shared by every shader through the `Frame` uniform
+// block declared in shaders/frame.glsl. Members are in the block's order
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameUniforms {
+    pub view: Mat4<f32>,
+    pub projection: Mat4<f32>,
+    pub camera_position: Vec3<f32>,
+    // Seconds since the viewer started
+    pub time: f32,
+    // Unit vector pointing towards the sun
+    pub sun_direction: Vec3<f32>,
+    // Exponential fog, 0 turns it off
+    pub fog_density: f32,
+    pub sun_color: Vec3<f32>,
+    pub fog_color: Vec3<f32>,
+}
+
+impl Default for FrameUniforms {
+    fn default() -> Self {
+        FrameUniforms {
+            view: Mat4::identity(),
+            projection: Mat4::identity(),
+            camera_position: Vec3::zero(),
+            time: 0.0,
+            sun_direction: Vec3::new(0.3, 1.0, 0.2).normalized(),
+            fog_density: 0.0,
+            sun_color: Vec3::one(),
+            fog_color: Vec3::one(),
+        }
+    }
+}
+
+impl UniformBlock for FrameUniforms {
+    const NAME: &'static str = "Frame";
+
+    fn visit<V: BlockVisitor>(&self, visitor: &mut V) {
+        visitor.member("view", &self.view);
+        visitor.member("projection", &self.projection);
+        visitor.member("camera_position", &self.camera_position);
+        visitor.member("time", &self.time);
+        visitor.member("sun_direction", &self.sun_direction);
+        visitor.member("fog_density", &self.fog_density);
+        visitor.member("sun_color", &self.sun_color);
+        visitor.member("fog_color", &self.fog_color);
+    }
+}
@@ -0,0 +1,124 @@
+use vek::mat::Mat4;
+use vek::vec::{Vec3, Vec4};
+
+use crate::mesh::aabb::Aabb;
+
+//
+// A plane as `normal . p + distance = 0`, with the inside of the frustum on the side the
+// normal points to
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    // Normalizes `coefficients` (a, b, c, d) of ax + by + cz + d = 0
+    fn from_coefficients(coefficients: Vec4<f32>) -> Self {
+        let normal: Vec3<f32> = Vec3::from(coefficients);
+        let length = normal.magnitude();
+
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    // Distance from the plane, positive on the inside
+    pub fn signed_distance(&self, point: Vec3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+// Where a bounding box is relative to a frustum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+//
+// The six planes bounding what a camera sees, in world space: left, right, bottom, top, near
+// and far
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    //
+    // Extracts the planes from `projection * view` (Gribb and Hartmann, "Fast Extraction of
+    // Viewing Frustum Planes from the World-View-Projection Matrix"). The near plane is the
+    // one GL clips against, z >= -w, which also holds everything a zero-to-one projection
+    // such as Mat4::perspective_rh_zo puts in front of its near plane
+    //
+    pub fn from_matrix(view_projection: &Mat4<f32>) -> Self {
+        let [x, y, z, w] = view_projection.into_row_arrays().map(Vec4::from);
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z].map(Plane::from_coefficients),
+        }
+    }
+
+    pub fn planes(&self) -> &[Plane; 6] {
+        &self.planes
+    }
+
+    pub fn contains(&self, point: Vec3<f32>) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    //
+    // Whether `aabb` is outside, partly inside or entirely inside the frustum. Only checks the
+    // box against each plane, so a box near a corner of the frustum but outside it can be
+    // reported as intersecting; boxes reported outside are always outside
+    //
+    pub fn classify(&self, aabb: &Aabb) -> Containment {
+        let (center, half) = (aabb.center(), aabb.half_extents());
+        let mut containment = Containment::Inside;
+
+        for plane in &self.planes {
+            // How far the box reaches towards and away from the plane around its centre
+            let reach = plane.normal.map(f32::abs).dot(half);
+            let distance = plane.signed_distance(center);
+
+            if distance < -reach {
+                return Containment::Outside;
+            }
+            if distance < reach {
+                containment = Containment::Intersecting;
+            }
+        }
+
+        containment
+    }
+
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.classify(aabb) != Containment::Outside
+    }
+}
+
+//
+// Objects drawn and skipped over a frame, see Frustum and Object::is_visible
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl CullStats {
+    // Counts an object as drawn or culled, passing `visible` through
+    pub fn count(&mut self, visible: bool) -> bool {
+        if visible {
+            self.drawn += 1;
+        } else {
+            self.culled += 1;
+        }
+
+        visible
+    }
+}
@@ -1,15 +1,33 @@
 #![allow(dead_code, unused_imports)]
 pub mod camera;
+pub mod dialect;
 pub mod drawable;
+pub mod error;
+pub mod frame_uniforms;
+pub mod frustum;
 mod gl_gen;
 pub mod object;
+pub mod preprocessor;
 pub mod renderer;
+pub mod ring_buffer;
 pub mod shader;
 pub mod texture;
+pub mod uniform;
+pub mod uniform_buffer;
+pub mod vertex_layout;
 
 pub use self::camera::*;
+pub use self::dialect::*;
 pub use self::drawable::*;
+pub use self::error::*;
+pub use self::frame_uniforms::*;
+pub use self::frustum::*;
 pub use self::object::*;
+pub use self::preprocessor::*;
 pub use self::renderer::*;
+pub use self::ring_buffer::*;
 pub use self::shader::*;
 pub use self::texture::*;
+pub use self::uniform::*;
+pub use self::uniform_buffer::*;
+pub use self::vertex_layout::*;
@@ -1,3 +1,5 @@
+use std::mem;
+
 use vek::mat::Mat4;
 use vek::vec::Vec3;
 
@@ -5,7 +7,12 @@ use crate::backend::drawable::{
     ArrayBuffer, Buffer, BufferHint, DrawPrimitive, DrawType, Drawable, DrawableAttributes,
     ElementBuffer, VertexArrayObject,
 };
+use crate::backend::error::{check_gl_error, Error, Result};
+use crate::backend::frustum::Frustum;
 use crate::backend::gl_gen::gl;
+use crate::backend::ring_buffer::RingBuffer;
+use crate::backend::vertex_layout::{VertexAttribute, Vertices};
+use crate::mesh::aabb::Aabb;
 
 use crate::backend::texture::Texture;
 
@@ -32,78 +39,183 @@ impl Transform {
     }
 }
 
+// Where an Object keeps its vertices
+enum VertexStorage {
+    Buffer(ArrayBuffer),
+    Ring(RingBuffer),
+}
+
 pub struct Object {
     vao: VertexArrayObject,
+    vertices: VertexStorage,
+    indices: Option<ElementBuffer>,
+    // Layout of the vertices, updates must match it
+    stride: usize,
+    attributes: Vec<VertexAttribute>,
+    vertex_count: usize,
+    hint: BufferHint,
     draw_count: usize,
     transform: Transform,
     draw_type: DrawType,
     draw_primitive: DrawPrimitive,
+    // Box around the vertices before the transform, None to always draw the object
+    bounds: Option<Aabb>,
     texture: Option<Texture>,
 }
 
 #[allow(dead_code)]
 impl Object {
-    pub fn new<D: Drawable>(drawable: D, origin: Vec3<f32>, texture: Option<Texture>) -> Self {
-        let mut vao = VertexArrayObject::new();
+    pub fn new<D: Drawable>(drawable: D, origin: Vec3<f32>, texture: Option<Texture>) -> Result<Self> {
+        Self::with_hint(drawable, origin, texture, BufferHint::STATIC)
+    }
+
+    //
+    // Creates an object whose buffers are expected to change as `hint` says. Objects with
+    // BufferHint::STREAM keep their vertices in a persistently mapped RingBuffer where the
+    // context supports one
+    //
+    pub fn with_hint<D: Drawable>(
+        drawable: D,
+        origin: Vec3<f32>,
+        texture: Option<Texture>,
+        hint: BufferHint,
+    ) -> Result<Self> {
+        let mut object = Object {
+            vao: VertexArrayObject::new(),
+            vertices: VertexStorage::Buffer(ArrayBuffer::new()),
+            indices: None,
+            stride: 0,
+            attributes: Vec::new(),
+            vertex_count: 0,
+            hint,
+            draw_count: 0,
+            transform: Transform::new(origin),
+            draw_type: DrawType::BUFFERED,
+            draw_primitive: DrawPrimitive::TRIANGLES,
+            bounds: None,
+            texture,
+        };
 
-        vao.bind();
+        object.replace(&drawable)?;
 
+        Ok(object)
+    }
+
+    //
+    // Reuploads everything from `drawable`, which may have a different size and layout,
+    // reallocating the buffers but keeping the vertex array
+    //
+    pub fn replace<D: Drawable>(&mut self, drawable: &D) -> Result<()> {
         let DrawableAttributes {
             buffer,
             draw_count,
             draw_primitive,
-        } = Drawable::vertex_attributes(&drawable);
+            bounds,
+        } = Drawable::vertex_attributes(drawable);
+
+        let (vertices, indices) = match buffer {
+            Buffer::ArrayBuffer { vertices } => (vertices, None),
+            Buffer::IndexBuffer { vertices, indices } => (vertices, Some(indices)),
+        };
 
-        let mut vbo = ArrayBuffer::new();
+        self.vao.bind();
 
-        let transform = Transform::new(origin);
+        // Attributes of the previous layout would otherwise keep reading the old buffer
+        for attribute in &self.attributes {
+            unsafe {
+                gl::DisableVertexAttribArray(attribute.location);
+            }
+        }
 
-        let draw_type = match buffer {
-            Buffer::ArrayBuffer {
-                vertex_attributes,
-                vertex_attribute_pointers,
-            } => {
-                vbo.bind_data(
-                    vertex_attributes,
-                    vertex_attribute_pointers,
-                    BufferHint::STATIC,
-                );
-                vao.attach_buffer(vbo);
+        if self.hint == BufferHint::STREAM && RingBuffer::supported() {
+            self.vertices = VertexStorage::Ring(RingBuffer::new(&vertices)?);
+        } else {
+            if let VertexStorage::Ring(_) = self.vertices {
+                self.vertices = VertexStorage::Buffer(ArrayBuffer::new());
+            }
+            if let VertexStorage::Buffer(vbo) = &mut self.vertices {
+                vbo.bind_vertices(&vertices, self.hint);
+            }
+        }
 
+        self.draw_type = match indices {
+            Some(indices) => {
+                self.indices.get_or_insert_with(ElementBuffer::new).bind_data(indices, self.hint);
+                DrawType::INDEXED
+            }
+            None => {
+                self.indices = None;
                 DrawType::BUFFERED
             }
-            Buffer::IndexBuffer {
-                vertex_attributes,
-                vertex_attribute_pointers,
-                indices,
-            } => {
-                vbo.bind_data(
-                    vertex_attributes,
-                    vertex_attribute_pointers,
-                    BufferHint::STATIC,
-                );
-                vao.attach_buffer(vbo);
+        };
 
-                let mut ebo = ElementBuffer::new();
+        self.stride = vertices.stride();
+        self.attributes = vertices.attributes().to_vec();
+        self.vertex_count = vertices.len();
+        self.draw_count = draw_count;
+        self.draw_primitive = draw_primitive;
+        self.bounds = bounds;
 
-                ebo.bind_data(indices, BufferHint::STATIC);
+        // Uploading the buffers fails if GL runs out of memory
+        check_gl_error()
+    }
 
-                vao.attach_buffer(ebo);
+    //
+    // Overwrites the vertices from index `first` on, e.g. the part of a mesh built from the
+    // region of a heightfield that was edited. The vertices must have the layout the object
+    // was created with
+    //
+    pub fn update_vertices(&mut self, first: usize, vertices: &Vertices) -> Result<()> {
+        if vertices.stride() != self.stride || vertices.attributes() != self.attributes.as_slice() {
+            return Err(Error::VertexLayoutMismatch);
+        }
 
-                DrawType::INDEXED
-            }
-        };
+        let offset = first * self.stride;
 
-        Object {
-            vao,
-            draw_count,
-            transform,
-            draw_type,
-            draw_primitive,
-            texture,
+        match &mut self.vertices {
+            VertexStorage::Buffer(vbo) => vbo.update_bytes(offset, vertices.data()),
+            VertexStorage::Ring(ring) => ring.update_bytes(offset, vertices.data()),
         }
     }
 
+    // Overwrites the indices from position `first` on
+    pub fn update_indices(&mut self, first: usize, indices: &[u32]) -> Result<()> {
+        match &mut self.indices {
+            Some(ebo) => {
+                self.vao.bind();
+                ebo.update(first, indices)
+            }
+            None => Err(Error::BufferRange {
+                offset: first * mem::size_of::<u32>(),
+                length: mem::size_of_val(indices),
+                size: 0,
+            }),
+        }
+    }
+
+    // World-space box around the object, None if it has none and is never culled
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds.map(|bounds| bounds.transformed(&self.get_transform()))
+    }
+
+    //
+    // Replaces the box around the vertices, before the transform. Updating vertices does not
+    // touch it, set it again when they may have moved outside it
+    //
+    pub fn set_bounds(&mut self, bounds: Option<Aabb>) {
+        self.bounds = bounds;
+    }
+
+    // Whether any of the object may be inside `frustum`, always true without bounds
+    pub fn is_visible(&self, frustum: &Frustum) -> bool {
+        self.bounds().is_none_or(|bounds| frustum.intersects(&bounds))
+    }
+
+    // Whether the vertices live in a persistently mapped RingBuffer
+    pub fn is_persistently_mapped(&self) -> bool {
+        matches!(self.vertices, VertexStorage::Ring(_))
+    }
+
     pub fn transform(&mut self, transform_type: TransformType) {
         let transform = &mut self.transform;
 
@@ -136,18 +248,25 @@ impl Object {
     }
 
     pub fn draw(&self) {
+        // A ring buffer holds several copies of the vertices, the draw picks one by offsetting
+        // the vertex indices
+        let base_vertex = match &self.vertices {
+            VertexStorage::Buffer(_) => 0,
+            VertexStorage::Ring(ring) => ring.draw_segment() * self.vertex_count,
+        };
+
         unsafe {
-            if self.texture.is_some() {
-                self.texture.as_ref().unwrap().bind();
+            if let Some(texture) = &self.texture {
+                texture.bind();
             }
 
             self.vao.bind();
 
             match self.draw_type {
                 DrawType::BUFFERED => {
-                    gl::DrawArrays(self.draw_primitive.bits(), 0, self.draw_count as _);
+                    gl::DrawArrays(self.draw_primitive.bits(), base_vertex as _, self.draw_count as _);
                 }
-                DrawType::INDEXED => {
+                DrawType::INDEXED if base_vertex == 0 => {
                     gl::DrawElements(
                         self.draw_primitive.bits(),
                         self.draw_count as _,
@@ -155,8 +274,21 @@ impl Object {
                         std::ptr::null(),
                     );
                 }
+                DrawType::INDEXED => {
+                    gl::DrawElementsBaseVertex(
+                        self.draw_primitive.bits(),
+                        self.draw_count as _,
+                        gl::UNSIGNED_INT,
+                        std::ptr::null(),
+                        base_vertex as _,
+                    );
+                }
                 _ => unreachable!(),
             }
         }
+
+        if let VertexStorage::Ring(ring) = &self.vertices {
+            ring.fence();
+        }
     }
 }
@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backend::dialect::GlslVersion;
+use crate::backend::error::*;
+
+//
+// GLSL source after `#include "file"` directives have been resolved and `#define`s injected,
+// remembering which file and line every line of the output came from so compile logs can
+// point at the original sources
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreprocessedSource {
+    source: String,
+    // Every file read, the shader itself first
+    files: Vec<PathBuf>,
+    // Index into `files` and 1-based line for every output line, None for injected lines
+    lines: Vec<Option<(usize, u32)>>,
+    // Vertex inputs that lost their layout location in translation, to bind before linking
+    attributes: Vec<(u32, String)>,
+}
+
+impl PreprocessedSource {
+    //
+    // Reads the shader at `path`, replacing each `#include "file.glsl"` line with the contents
+    // of that file, relative to the directory of the file doing the including. Every file is
+    // included at most once, so shared code needs no include guards. `defines` are inserted as
+    // `#define NAME VALUE` lines right after the `#version` line
+    //
+    pub fn load<P: AsRef<Path>>(path: P, defines: &[(&str, &str)]) -> Result<Self> {
+        let mut preprocessed = Self::empty(Vec::new());
+
+        let mut defines = Some(defines);
+        preprocessed.include(path.as_ref(), &mut Vec::new(), &mut defines)?;
+
+        // Without a #version line the defines go first, GLSL then assumes version 110
+        if let Some(defines) = defines {
+            let rest = std::mem::take(&mut preprocessed.source);
+            let lines = std::mem::take(&mut preprocessed.lines);
+            preprocessed.push_defines(defines);
+            preprocessed.source.push_str(&rest);
+            preprocessed.lines.extend(lines);
+        }
+
+        Ok(preprocessed)
+    }
+
+    // Source that did not come from a file, log lines map to "<string>"
+    pub fn from_string(source: &str) -> Self {
+        let mut preprocessed = Self::empty(vec![PathBuf::from("<string>")]);
+
+        for (number, line) in source.lines().enumerate() {
+            preprocessed.push_line(line, Some((0, number as u32 + 1)));
+        }
+
+        preprocessed
+    }
+
+    fn empty(files: Vec<PathBuf>) -> Self {
+        PreprocessedSource {
+            source: String::new(),
+            files,
+            lines: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    fn push_line(&mut self, line: &str, origin: Option<(usize, u32)>) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.lines.push(origin);
+    }
+
+    //
+    // Rewrites GLSL 3.30 source for another dialect, see GlslVersion::translate_line. Anything
+    // the dialect needs up front, such as a precision statement, goes after the #version line
+    //
+    pub fn translate(&self, version: GlslVersion, stage: ShaderStage) -> Self {
+        let mut translated = Self::empty(self.files.clone());
+        let mut prelude = Some(version.prelude(stage, &self.source));
+        let mut in_block = false;
+
+        for (line, &origin) in self.source.lines().zip(&self.lines) {
+            let (line, attribute) = version.translate_line(line, stage, &mut in_block);
+            let is_version = line.trim_start().starts_with("#version");
+
+            translated.push_line(&line, origin);
+            translated.attributes.extend(attribute);
+
+            if is_version {
+                for line in prelude.take().unwrap_or_default() {
+                    translated.push_line(line, None);
+                }
+            }
+        }
+
+        translated
+    }
+
+    // `stack` holds the files currently being included, to catch include cycles
+    fn include(&mut self, path: &Path, stack: &mut Vec<PathBuf>, defines: &mut Option<&[(&str, &str)]>) -> Result<()> {
+        let source = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let file = self.files.len();
+        self.files.push(path.to_path_buf());
+        stack.push(path.to_path_buf());
+
+        for (number, line) in source.lines().enumerate() {
+            let number = number as u32 + 1;
+            let directive = line.trim_start();
+
+            if let Some(argument) = directive.strip_prefix("#include") {
+                let included = Self::include_path(path, argument).ok_or_else(|| Error::Preprocess {
+                    path: path.to_path_buf(),
+                    line: number,
+                    message: format!("expected #include \"file\", found `{}`", directive),
+                })?;
+
+                if stack.iter().any(|p| Self::same_file(p, &included)) {
+                    return Err(Error::Preprocess {
+                        path: path.to_path_buf(),
+                        line: number,
+                        message: format!("including {} again forms a cycle", included.display()),
+                    });
+                }
+
+                if !self.files.iter().any(|p| Self::same_file(p, &included)) {
+                    self.include(&included, stack, defines)?;
+                }
+                continue;
+            }
+
+            self.push_line(line, Some((file, number)));
+
+            if directive.starts_with("#version") {
+                if let Some(defines) = defines.take() {
+                    self.push_defines(defines);
+                }
+            }
+        }
+
+        stack.pop();
+        Ok(())
+    }
+
+    fn push_defines(&mut self, defines: &[(&str, &str)]) {
+        for (name, value) in defines {
+            self.push_line(&format!("#define {} {}", name, value), None);
+        }
+    }
+
+    // Path named by the argument of an #include, None if it is not a quoted file name
+    fn include_path(including: &Path, argument: &str) -> Option<PathBuf> {
+        let argument = argument.trim();
+        let name = argument.strip_prefix('"')?.strip_suffix('"')?;
+
+        if name.is_empty() {
+            return None;
+        }
+
+        Some(including.parent().unwrap_or_else(|| Path::new("")).join(name))
+    }
+
+    fn same_file(a: &Path, b: &Path) -> bool {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Every file the source was built from, to watch for changes
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // Locations to bind with glBindAttribLocation before linking
+    pub fn attribute_locations(&self) -> &[(u32, String)] {
+        &self.attributes
+    }
+
+    // File and line an output line (1-based) came from, None for injected lines
+    pub fn origin(&self, line: u32) -> Option<(&Path, u32)> {
+        let (file, line) = (*self.lines.get((line as usize).checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+
+    //
+    // Rewrites the line references in a driver's compile log to the original file and line.
+    // Understands the `0:12(5):` (Mesa), `ERROR: 0:12:` (AMD, Intel, ANGLE) and `0(12) :`
+    // (NVIDIA) styles, other lines are kept as they are
+    //
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.map_log_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn map_log_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+
+        for start in 0..bytes.len() {
+            // Only a "0" that starts a token is a source string number
+            if bytes[start] != b'0' || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+                continue;
+            }
+
+            let (open, close) = match bytes.get(start + 1) {
+                Some(b':') => (start + 1, None),
+                Some(b'(') => (start + 1, Some(b')')),
+                _ => continue,
+            };
+
+            let digits = bytes[open + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 {
+                continue;
+            }
+
+            let mut end = open + 1 + digits;
+            if let Some(close) = close {
+                if bytes.get(end) != Some(&close) {
+                    continue;
+                }
+                end += 1;
+            }
+
+            let number: usize = match line[open + 1..open + 1 + digits].parse() {
+                Ok(number) if (1..=self.lines.len()).contains(&number) => number,
+                _ => continue,
+            };
+
+            let location = match self.lines[number - 1] {
+                Some((file, original)) => format!("{}:{}", self.files[file].display(), original),
+                None => "<injected>".to_string(),
+            };
+            return format!("{}{}{}", &line[..start], location, &line[end..]);
+        }
+
+        line.to_string()
+    }
+}
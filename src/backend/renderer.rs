@@ -7,7 +7,10 @@ use std::ffi::CString;
 use std::num::NonZeroU32;
 use vek::vec::Vec4;
 
+use crate::backend::dialect::GlslVersion;
+use crate::backend::error::Result;
 use crate::backend::gl_gen::gl;
+use crate::mesh::PRIMITIVE_RESTART_INDEX;
 
 bitflags! {
     pub struct ClearFlags: u32 {
@@ -46,10 +49,28 @@ impl Renderer {
         surface: Surface<glutin::surface::WindowSurface>,
         gl_display: &D,
     ) -> Self {
-        let _ = gl::load_with(|symbol| {
+        gl::load_with(|symbol| {
             let symbol = CString::new(symbol).unwrap();
             gl_display.get_proc_address(symbol.as_c_str()).cast()
         });
+
+        // Shaders compiled from now on are translated for whichever context was created
+        let version = GlslVersion::detect();
+        GlslVersion::set_current(version);
+
+        // Strips split by PRIMITIVE_RESTART_INDEX, see GridMeshBuilder. GLES only has the fixed
+        // index, which is the same one for u32 indices
+        unsafe {
+            match version {
+                GlslVersion::Glsl330 => {
+                    gl::Enable(gl::PRIMITIVE_RESTART);
+                    gl::PrimitiveRestartIndex(PRIMITIVE_RESTART_INDEX);
+                }
+                GlslVersion::GlslEs300 => gl::Enable(gl::PRIMITIVE_RESTART_FIXED_INDEX),
+                GlslVersion::Glsl120 => {}
+            }
+        }
+
         Self { context, surface }
     }
 
@@ -65,7 +86,12 @@ impl Renderer {
         }
     }
 
+    // Does nothing on GLES, which has no polygon modes
     pub fn polygon_mode(&self, mode: PolygonMode) {
+        if GlslVersion::current() == GlslVersion::GlslEs300 || !gl::PolygonMode::is_loaded() {
+            return;
+        }
+
         unsafe {
             gl::PolygonMode(gl::FRONT_AND_BACK, mode.bits());
         }
@@ -90,9 +116,7 @@ impl Renderer {
         unsafe { gl::GetError() }
     }
 
-    pub fn swap_buffers(&self) {
-        self.surface
-            .swap_buffers(&self.context)
-            .expect("Unable to swap buffers");
+    pub fn swap_buffers(&self) -> Result<()> {
+        Ok(self.surface.swap_buffers(&self.context)?)
     }
 }
@@ -0,0 +1,212 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::ptr;
+
+use crate::backend::dialect::GlslVersion;
+use crate::backend::drawable::point_attributes;
+use crate::backend::error::{check_gl_error, Error, Result};
+use crate::backend::gl_gen::gl;
+use crate::backend::gl_gen::gl::types::*;
+use crate::backend::vertex_layout::Vertices;
+
+// Copies of the vertices kept in flight: one the GPU may still be reading, one being drawn
+// and one being written
+pub const RING_SEGMENTS: usize = 3;
+
+// How long to wait on a fence before flushing and waiting again, in nanoseconds
+const FENCE_TIMEOUT: GLuint64 = 1_000_000;
+
+//
+// Vertex buffer for data rewritten often, e.g. terrain being eroded, holding RING_SEGMENTS
+// copies of the vertices in one persistently mapped buffer. Writes go straight into a copy
+// the GPU is done with, guarded by a fence placed after the draws reading it, so updating
+// never stalls on the driver. Draws pick their copy with a base vertex, see draw_segment.
+//
+// The first write after a draw copies every vertex into the next copy, further writes before
+// the next draw only touch the bytes they change. Needs glBufferStorage (GL 4.4 or
+// ARB_buffer_storage), see supported; elsewhere use an ArrayBuffer with BufferHint::STREAM
+//
+pub struct RingBuffer {
+    id: GLuint,
+    mapped: *mut u8,
+    // Bytes in one copy of the vertices
+    segment_size: usize,
+    // Latest contents of the vertices
+    contents: Vec<u8>,
+    // Copy drawn, and the one being written if it changed since the last draw
+    current: Cell<usize>,
+    pending: Cell<Option<usize>>,
+    fences: [Cell<GLsync>; RING_SEGMENTS],
+}
+
+impl RingBuffer {
+    // Whether the current context can persistently map buffers
+    pub fn supported() -> bool {
+        // The base vertex draws and fences are core from 3.2, only used with GLSL 3.30
+        if GlslVersion::current() != GlslVersion::Glsl330 || !gl::BufferStorage::is_loaded() {
+            return false;
+        }
+
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+
+        (major, minor) >= (4, 4) || Self::has_extension("GL_ARB_buffer_storage")
+    }
+
+    fn has_extension(name: &str) -> bool {
+        let mut count = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        }
+
+        (0..count.max(0) as GLuint).any(|index| {
+            let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+            !extension.is_null() && unsafe { CStr::from_ptr(extension as *const _) }.to_bytes() == name.as_bytes()
+        })
+    }
+
+    // Allocates the ring, fills the first copy with the vertices and points their attributes
+    // at it, with the vertex array they belong to bound
+    pub fn new(vertices: &Vertices) -> Result<Self> {
+        let segment_size = vertices.data().len();
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+        let mut id = 0;
+        let mapped = unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::ARRAY_BUFFER, id);
+            // Zero sized buffers cannot be mapped
+            gl::BufferStorage(
+                gl::ARRAY_BUFFER,
+                (segment_size * RING_SEGMENTS).max(1) as _,
+                ptr::null(),
+                flags,
+            );
+            let mapped = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, (segment_size * RING_SEGMENTS).max(1) as _, flags);
+
+            point_attributes(vertices.stride(), vertices.attributes());
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            mapped as *mut u8
+        };
+
+        let ring = RingBuffer {
+            id,
+            mapped,
+            segment_size,
+            contents: vertices.data().to_vec(),
+            current: Cell::new(0),
+            pending: Cell::new(None),
+            fences: std::array::from_fn(|_| Cell::new(ptr::null())),
+        };
+
+        check_gl_error()?;
+        if mapped.is_null() {
+            return Err(Error::Gl { code: gl::INVALID_OPERATION });
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(ring.contents.as_ptr(), ring.mapped, segment_size);
+        }
+
+        Ok(ring)
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    // Bytes in one copy of the vertices
+    pub fn size(&self) -> usize {
+        self.segment_size
+    }
+
+    // Overwrites the bytes from `offset` on in the copy the next draw uses
+    pub fn update_bytes(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        if offset.checked_add(data.len()).is_none_or(|end| end > self.segment_size) {
+            return Err(Error::BufferRange {
+                offset,
+                length: data.len(),
+                size: self.segment_size,
+            });
+        }
+
+        self.contents[offset..offset + data.len()].copy_from_slice(data);
+
+        match self.pending.get() {
+            // Already copied since the last draw, only the change is written
+            Some(segment) => self.write(segment, offset, data),
+            None => {
+                let segment = (self.current.get() + 1) % RING_SEGMENTS;
+                self.wait(segment);
+                self.write(segment, 0, &self.contents);
+                self.pending.set(Some(segment));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, segment: usize, offset: usize, data: &[u8]) {
+        unsafe {
+            let target = self.mapped.add(segment * self.segment_size + offset);
+            ptr::copy_nonoverlapping(data.as_ptr(), target, data.len());
+        }
+    }
+
+    // Blocks until the GPU is done with the draws reading `segment`
+    fn wait(&self, segment: usize) {
+        let fence = self.fences[segment].replace(ptr::null());
+        if fence.is_null() {
+            return;
+        }
+
+        unsafe {
+            while let gl::TIMEOUT_EXPIRED = gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT) {}
+            gl::DeleteSync(fence);
+        }
+    }
+
+    //
+    // Copy of the vertices the next draw should read, the base vertex to draw with is this
+    // times the number of vertices. Call fence once the draw is issued
+    //
+    pub fn draw_segment(&self) -> usize {
+        if let Some(segment) = self.pending.take() {
+            self.current.set(segment);
+        }
+
+        self.current.get()
+    }
+
+    // Marks the copy being drawn as in use until the draws issued so far are done
+    pub fn fence(&self) {
+        let segment = self.current.get();
+
+        unsafe {
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            let previous = self.fences[segment].replace(fence);
+            if !previous.is_null() {
+                gl::DeleteSync(previous);
+            }
+        }
+    }
+}
+
+impl Drop for RingBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            for fence in &self.fences {
+                if !fence.get().is_null() {
+                    gl::DeleteSync(fence.get());
+                }
+            }
+
+            // Deleting the buffer unmaps it
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -1,67 +1,233 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ffi::CString;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::str;
+use std::time::{Duration, Instant, SystemTime};
 
-use vek::mat::Mat4;
-use vek::vec::Vec3;
+use vek::mat::{Mat3, Mat4};
+use vek::vec::{Vec2, Vec3, Vec4};
 
+use crate::backend::dialect::GlslVersion;
+use crate::backend::error::{Error, Result, ShaderStage};
+use crate::backend::preprocessor::PreprocessedSource;
+use crate::backend::uniform::{Uniform, Uniforms};
+use crate::backend::uniform_buffer::{uniform_block_binding, BlockVisitor, Std140, UniformBlock};
 use crate::backend::gl_gen::gl;
 use crate::backend::gl_gen::gl::types::*;
 
-#[derive(Clone, Debug)]
+// How often the source files of a watched shader are checked for changes
+pub const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+//
+// Source files behind a shader created with `from_file`, including everything they #include,
+// along with when each was last modified as of the last compile
+//
+#[derive(Debug)]
+struct ShaderSources {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    defines: Vec<(String, String)>,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl ShaderSources {
+    // A file that cannot be read right now, e.g. while an editor replaces it, has no time
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn changed(&self) -> bool {
+        let modified: Vec<_> = self.files.iter().map(|(path, _)| Self::modified(path)).collect();
+
+        modified.iter().all(Option::is_some) && self.files.iter().zip(&modified).any(|((_, before), now)| before != now)
+    }
+
+    // Preprocesses and compiles both stages, then watches every file that was read
+    fn compile(&mut self) -> Result<Shader> {
+        // Timestamps are taken first so a save during the compile is picked up by the next poll
+        let before: Vec<_> = self.files.iter().map(|(path, _)| (path.clone(), Self::modified(path))).collect();
+
+        let defines: Vec<_> = self.defines.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        let vertex = PreprocessedSource::load(&self.vertex, &defines);
+        let fragment = PreprocessedSource::load(&self.fragment, &defines);
+
+        self.files = before.clone();
+        if let (Ok(vertex), Ok(fragment)) = (&vertex, &fragment) {
+            self.files.clear();
+
+            for path in vertex.files().iter().chain(fragment.files()) {
+                if self.files.iter().all(|(watched, _)| watched != path) {
+                    let modified = match before.iter().find(|(watched, _)| watched == path) {
+                        Some(&(_, modified)) => modified,
+                        None => Self::modified(path),
+                    };
+                    self.files.push((path.clone(), modified));
+                }
+            }
+        }
+
+        Shader::from_sources(&vertex?, &fragment?)
+    }
+}
+
+#[derive(Debug)]
 pub struct Shader {
     program: GLuint,
+    sources: Option<ShaderSources>,
+    uniforms: Uniforms,
+    // Warn about uniforms set from Rust that the shader does not have, see set_uniform
+    debug_uniforms: bool,
+    warned: RefCell<HashSet<String>>,
 }
 
 impl Shader {
     #[allow(dead_code)]
-    pub fn from_string<'a>(vertex: &'a str, fragment: &'a str) -> Shader {
+    pub fn from_string<'a>(vertex: &'a str, fragment: &'a str) -> Result<Shader> {
+        Self::from_sources(&PreprocessedSource::from_string(vertex), &PreprocessedSource::from_string(fragment))
+    }
+
+    //
+    // Compiles GLSL 3.30 sources, translated for the dialect of the current context first.
+    // Compile errors point at the file and line they came from rather than the combined source
+    //
+    fn from_sources(vertex: &PreprocessedSource, fragment: &PreprocessedSource) -> Result<Shader> {
+        let version = GlslVersion::current();
+        let vertex = vertex.translate(version, ShaderStage::Vertex);
+        let fragment = fragment.translate(version, ShaderStage::Fragment);
+
+        let map_log = |e| match e {
+            Error::Compile { stage, log } => {
+                let source = match stage {
+                    ShaderStage::Vertex => &vertex,
+                    ShaderStage::Fragment => &fragment,
+                };
+                Error::Compile {
+                    stage,
+                    log: source.map_log(&log),
+                }
+            }
+            e => e,
+        };
+
         // Create GLSL shaders
-        let vs = Self::compile_shader(vertex, gl::VERTEX_SHADER);
-        let fs = Self::compile_shader(fragment, gl::FRAGMENT_SHADER);
+        let vs = Self::compile_shader(vertex.source(), ShaderStage::Vertex).map_err(map_log)?;
+        let fs = match Self::compile_shader(fragment.source(), ShaderStage::Fragment) {
+            Ok(fs) => fs,
+            Err(e) => {
+                unsafe { gl::DeleteShader(vs) };
+                return Err(map_log(e));
+            }
+        };
 
-        let program = Self::link_program(vs, fs);
+        let program = Self::link_program(vs, fs, vertex.attribute_locations());
 
         unsafe {
             gl::DeleteShader(fs);
             gl::DeleteShader(vs);
         }
 
-        Shader { program }
+        let program = program?;
+
+        let shader = Shader {
+            program,
+            sources: None,
+            uniforms: Uniforms::reflect(program),
+            debug_uniforms: cfg!(debug_assertions),
+            warned: RefCell::new(HashSet::new()),
+        };
+        shader.bind_uniform_blocks();
+
+        Ok(shader)
+    }
+
+    // Points every uniform block at the binding point its UniformBuffer reserved
+    fn bind_uniform_blocks(&self) {
+        if !GlslVersion::current().supports_uniform_buffers() {
+            return;
+        }
+
+        unsafe {
+            let mut count = 0;
+            gl::GetProgramiv(self.program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+
+            let mut max_length = 0;
+            gl::GetProgramiv(self.program, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH, &mut max_length);
+
+            for index in 0..count.max(0) as GLuint {
+                let mut buf = vec![0u8; max_length.max(1) as usize];
+                let mut length = 0;
+
+                gl::GetActiveUniformBlockName(self.program, index, max_length, &mut length, buf.as_mut_ptr() as *mut GLchar);
+                buf.truncate(length.max(0) as usize);
+                let name = String::from_utf8_lossy(&buf).into_owned();
+
+                match uniform_block_binding(&name) {
+                    Some(binding) => gl::UniformBlockBinding(self.program, index, binding),
+                    None => self.warn(&name, || format!("no UniformBuffer was created for uniform block `{}`", name)),
+                }
+            }
+        }
     }
 
     #[allow(dead_code)]
-    pub fn from_file<'a>(vertex: &'a str, fragment: &'a str) -> Shader {
-        let vertex_glsl = File::open(vertex).expect("Could not open vertex path");
-        let fragment_glsl = File::open(fragment).expect("Could not open fragment path");
-
-        let mut buf_reader = BufReader::new(vertex_glsl);
-        let mut vertex_shader = String::new();
-        buf_reader
-            .read_to_string(&mut vertex_shader)
-            .expect("Could not read vertex path");
-
-        let mut buf_reader = BufReader::new(fragment_glsl);
-        let mut fragment_shader = String::new();
-        buf_reader
-            .read_to_string(&mut fragment_shader)
-            .expect("Could not read fragment path");
+    pub fn from_file<P: AsRef<Path>>(vertex: P, fragment: P) -> Result<Shader> {
+        Self::from_file_with_defines(vertex, fragment, &[])
+    }
 
-        // Create GLSL shaders
-        let vs = Self::compile_shader(vertex_shader.as_str(), gl::VERTEX_SHADER);
-        let fs = Self::compile_shader(fragment_shader.as_str(), gl::FRAGMENT_SHADER);
+    //
+    // Loads a shader through the preprocessor, see PreprocessedSource, with `defines` injected
+    // into both stages, e.g. `&[("FOG", "1")]` to switch on `#ifdef FOG` blocks
+    //
+    pub fn from_file_with_defines<P: AsRef<Path>>(vertex: P, fragment: P, defines: &[(&str, &str)]) -> Result<Shader> {
+        let (vertex, fragment) = (vertex.as_ref(), fragment.as_ref());
+
+        let mut sources = ShaderSources {
+            vertex: vertex.to_path_buf(),
+            fragment: fragment.to_path_buf(),
+            defines: defines.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect(),
+            files: vec![(vertex.to_path_buf(), None), (fragment.to_path_buf(), None)],
+            last_poll: Instant::now(),
+        };
+
+        let mut shader = sources.compile()?;
+        shader.sources = Some(sources);
+
+        Ok(shader)
+    }
 
-        let program = Self::link_program(vs, fs);
+    //
+    // Recompiles a shader created with `from_file` when one of its source files or anything
+    // they include has changed, checking at most once every SHADER_POLL_INTERVAL. The new
+    // program replaces the current one only if it compiles and links, otherwise the current
+    // one is kept and the error is returned. Returns None when nothing changed. Must be called
+    // on the thread owning the GL context, once per frame is enough
+    //
+    pub fn reload_if_changed(&mut self) -> Option<Result<()>> {
+        let sources = self.sources.as_mut()?;
+
+        if sources.last_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return None;
+        }
+        sources.last_poll = Instant::now();
 
-        unsafe {
-            gl::DeleteShader(fs);
-            gl::DeleteShader(vs);
+        if !sources.changed() {
+            return None;
         }
 
-        Shader { program }
+        // The timestamps are updated even if this fails, a broken edit is only reported once
+        let reloaded = sources.compile();
+
+        Some(reloaded.map(|reloaded| {
+            unsafe {
+                gl::DeleteProgram(self.program);
+            }
+            self.program = reloaded.program;
+            self.uniforms = reloaded.uniforms;
+            self.warned.borrow_mut().clear();
+        }))
     }
 
     #[allow(dead_code)]
@@ -77,14 +243,17 @@ impl Shader {
     }
 
     #[allow(dead_code)]
-    fn compile_shader(src: &str, ty: GLenum) -> GLuint {
-        let shader;
+    fn compile_shader(src: &str, stage: ShaderStage) -> Result<GLuint> {
+        // Sources with a nul byte cannot be handed to GL, report them like any other bad source
+        let c_str = CString::new(src.as_bytes()).map_err(|_| Error::Compile {
+            stage,
+            log: "Shader source contains a nul character".to_string(),
+        })?;
 
         unsafe {
-            shader = gl::CreateShader(ty);
+            let shader = gl::CreateShader(stage.gl_enum());
 
             // Attempt to compile the shader
-            let c_str = CString::new(src.as_bytes()).unwrap();
             gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
             gl::CompileShader(shader);
 
@@ -97,8 +266,7 @@ impl Shader {
                 let mut len = 0;
                 gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
 
-                let mut buf = Vec::with_capacity(len as usize);
-                buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+                let mut buf = vec![0u8; len.max(1) as usize];
 
                 gl::GetShaderInfoLog(
                     shader,
@@ -106,25 +274,35 @@ impl Shader {
                     ptr::null_mut(),
                     buf.as_mut_ptr() as *mut GLchar,
                 );
+                buf.pop(); // skip the trailing null character
 
-                panic!(
-                    "\n{}",
-                    str::from_utf8(&buf).expect("ShaderInfoLog not valid utf8")
-                );
+                gl::DeleteShader(shader);
+
+                return Err(Error::Compile {
+                    stage,
+                    log: String::from_utf8_lossy(&buf).into_owned(),
+                });
             }
+
+            Ok(shader)
         }
-        shader
     }
 
     #[allow(dead_code)]
-    fn link_program(vs: GLuint, fs: GLuint) -> GLuint {
-        let program;
-
+    fn link_program(vs: GLuint, fs: GLuint, attributes: &[(u32, String)]) -> Result<GLuint> {
         unsafe {
-            program = gl::CreateProgram();
+            let program = gl::CreateProgram();
 
             gl::AttachShader(program, vs);
             gl::AttachShader(program, fs);
+
+            // Inputs that had a layout location before translation
+            for (location, name) in attributes {
+                if let Ok(name) = CString::new(name.as_str()) {
+                    gl::BindAttribLocation(program, *location, name.as_ptr());
+                }
+            }
+
             gl::LinkProgram(program);
 
             // Get the link status
@@ -136,49 +314,133 @@ impl Shader {
                 let mut len: GLint = 0;
                 gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
 
-                let mut buf = Vec::with_capacity(len as usize);
-                buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+                let mut buf = vec![0u8; len.max(1) as usize];
                 gl::GetProgramInfoLog(
                     program,
                     len,
                     ptr::null_mut(),
                     buf.as_mut_ptr() as *mut GLchar,
                 );
+                buf.pop(); // skip the trailing null character
 
-                panic!(
-                    "{}",
-                    str::from_utf8(&buf).expect("ProgramInfoLog not valid utf8")
-                );
+                gl::DeleteProgram(program);
+
+                return Err(Error::Link {
+                    log: String::from_utf8_lossy(&buf).into_owned(),
+                });
             }
+
+            Ok(program)
         }
-        program
     }
 
-    pub fn set_mat4fv<S: Into<&'static str>>(&self, name: S, data: &Mat4<f32>) {
-        let data_name = CString::new(name.into()).unwrap();
+    // Active uniforms of the program, as reflected when it was linked
+    pub fn uniforms(&self) -> &Uniforms {
+        &self.uniforms
+    }
 
-        unsafe {
-            let mat_loc = gl::GetUniformLocation(self.program, data_name.as_ptr());
-            gl::UniformMatrix4fv(mat_loc, 1, gl::FALSE, data.as_col_ptr());
+    // On by default in debug builds
+    pub fn set_debug_uniforms(&mut self, debug: bool) {
+        self.debug_uniforms = debug;
+    }
+
+    //
+    // Sets a uniform of the bound program through the locations reflected at link time. In
+    // debug mode setting a uniform the program does not have, or one whose GLSL type does not
+    // match the value, prints a warning once per name instead of being silently ignored.
+    // Uniforms the driver optimised away count as missing
+    //
+    pub fn set_uniform<U: Uniform + ?Sized>(&self, name: &str, value: &U) {
+        match self.uniforms.get(name) {
+            Some(_) => self.set_active_uniform(name, value),
+            None => self.warn(name, || format!("the shader has no active uniform `{}`", name)),
         }
     }
 
-    pub fn set_vec3f<S: Into<&'static str>>(&self, name: S, data: &Vec3<f32>) {
-        let data_name = CString::new(name.into()).unwrap();
+    // Skips uniforms the program does not have without a warning
+    fn set_active_uniform<U: Uniform + ?Sized>(&self, name: &str, value: &U) {
+        let info = match self.uniforms.get(name) {
+            Some(info) => *info,
+            None => return,
+        };
+
+        if !value.accepts(info.kind) {
+            self.warn(name, || {
+                format!("uniform `{}` is a {}, which a {} cannot set", name, info.kind, std::any::type_name::<U>())
+            });
+            return;
+        }
 
-        unsafe {
-            let mat_loc = gl::GetUniformLocation(self.program, data_name.as_ptr());
-            gl::Uniform3fv(mat_loc, 1, data.as_slice().as_ptr());
+        if value.count() > info.size as usize {
+            self.warn(name, || format!("{} values set on uniform `{}` of {}", value.count(), name, info.size));
         }
+
+        value.upload(info.location);
     }
 
-    #[allow(non_snake_case)]
-    pub fn set_sampler2D<S: Into<&'static str>>(&self, name: S, data: u32) {
-        let data_name = CString::new(name.into()).unwrap();
+    //
+    // Sets the members of a uniform block one by one on contexts without uniform buffers
+    // (OpenGL 2.1), where they are plain uniforms. Does nothing elsewhere, the shader reads
+    // the block from its UniformBuffer. Members the shader does not use are skipped quietly
+    //
+    pub fn set_uniform_block<B: UniformBlock>(&self, block: &B) {
+        if GlslVersion::current().supports_uniform_buffers() {
+            return;
+        }
 
-        unsafe {
-            let sampler = gl::GetUniformLocation(self.program, data_name.as_ptr());
-            gl::Uniform1i(sampler, data as _);
+        block.visit(&mut UniformSetter(self));
+    }
+
+    fn warn<F: FnOnce() -> String>(&self, name: &str, message: F) {
+        if self.debug_uniforms && self.warned.borrow_mut().insert(name.to_string()) {
+            eprintln!("Warning: {}", message());
         }
     }
+
+    // Location of an active uniform, None if the program does not have it
+    pub fn uniform_location(&self, name: &str) -> Option<GLint> {
+        self.uniforms.get(name).map(|info| info.location)
+    }
+
+    pub fn set_1f(&self, name: &str, data: f32) {
+        self.set_uniform(name, &data);
+    }
+
+    pub fn set_1i(&self, name: &str, data: i32) {
+        self.set_uniform(name, &data);
+    }
+
+    pub fn set_vec2f(&self, name: &str, data: &Vec2<f32>) {
+        self.set_uniform(name, data);
+    }
+
+    pub fn set_vec3f(&self, name: &str, data: &Vec3<f32>) {
+        self.set_uniform(name, data);
+    }
+
+    pub fn set_vec4f(&self, name: &str, data: &Vec4<f32>) {
+        self.set_uniform(name, data);
+    }
+
+    pub fn set_mat3fv(&self, name: &str, data: &Mat3<f32>) {
+        self.set_uniform(name, data);
+    }
+
+    pub fn set_mat4fv(&self, name: &str, data: &Mat4<f32>) {
+        self.set_uniform(name, data);
+    }
+
+    // `unit` is the texture unit the sampler reads from, not the texture name
+    #[allow(non_snake_case)]
+    pub fn set_sampler2D(&self, name: &str, unit: u32) {
+        self.set_uniform(name, &(unit as i32));
+    }
+}
+
+struct UniformSetter<'a>(&'a Shader);
+
+impl BlockVisitor for UniformSetter<'_> {
+    fn member<T: Std140 + Uniform>(&mut self, name: &str, value: &T) {
+        self.0.set_active_uniform(name, value);
+    }
 }
@@ -1,19 +1,28 @@
 use std::path::Path;
 
+use crate::backend::error::{check_gl_error, Error, Result};
 use crate::backend::gl_gen::gl;
-use image::GenericImageView;
+use image::{GenericImageView, ImageError};
 
 pub struct Texture(gl::types::GLuint);
 
 impl Texture {
-    pub fn new<S: Into<String>>(path_name: S) -> (Self, gl::types::GLuint) {
-        let path_name = path_name.into();
-        let path = Path::new(&path_name);
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<(Self, gl::types::GLuint)> {
+        let path = path.as_ref();
 
-        let image =
-            image::open(&path).unwrap_or_else(|_| panic!("Unable to open file: {:?}", path));
+        let image = image::open(path).map_err(|e| match e {
+            ImageError::IoError(source) => Error::Io {
+                path: path.to_path_buf(),
+                source,
+            },
+            source => Error::Image {
+                path: path.to_path_buf(),
+                source,
+            },
+        })?;
 
-        let data = image.raw_pixels();
+        // Uploaded as RGB whatever the file stores, other layouts would be read out of bounds
+        let data = image.to_rgb().into_raw();
 
         let mut texture_id = 0;
         unsafe {
@@ -41,7 +50,10 @@ impl Texture {
             gl::GenerateMipmap(gl::TEXTURE_2D);
         }
 
-        (Self(texture_id), texture_id)
+        let texture = Self(texture_id);
+        check_gl_error()?;
+
+        Ok((texture, texture_id))
     }
 
     pub fn bind(&self) {
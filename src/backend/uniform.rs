@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+
+use vek::mat::{Mat3, Mat4};
+use vek::vec::{Vec2, Vec3, Vec4};
+
+use crate::backend::gl_gen::gl;
+use crate::backend::gl_gen::gl::types::*;
+
+//
+// GLSL type of an active uniform as reported by glGetActiveUniform
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    Bool,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    // Any type without a setter, e.g. mat2x3 or samplerCube
+    Other(GLenum),
+}
+
+impl UniformType {
+    pub fn from_gl(kind: GLenum) -> Self {
+        match kind {
+            gl::FLOAT => UniformType::Float,
+            gl::FLOAT_VEC2 => UniformType::Vec2,
+            gl::FLOAT_VEC3 => UniformType::Vec3,
+            gl::FLOAT_VEC4 => UniformType::Vec4,
+            gl::INT => UniformType::Int,
+            gl::INT_VEC2 => UniformType::IVec2,
+            gl::INT_VEC3 => UniformType::IVec3,
+            gl::INT_VEC4 => UniformType::IVec4,
+            gl::BOOL => UniformType::Bool,
+            gl::FLOAT_MAT3 => UniformType::Mat3,
+            gl::FLOAT_MAT4 => UniformType::Mat4,
+            gl::SAMPLER_2D => UniformType::Sampler2D,
+            other => UniformType::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for UniformType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformType::Float => f.write_str("float"),
+            UniformType::Vec2 => f.write_str("vec2"),
+            UniformType::Vec3 => f.write_str("vec3"),
+            UniformType::Vec4 => f.write_str("vec4"),
+            UniformType::Int => f.write_str("int"),
+            UniformType::IVec2 => f.write_str("ivec2"),
+            UniformType::IVec3 => f.write_str("ivec3"),
+            UniformType::IVec4 => f.write_str("ivec4"),
+            UniformType::Bool => f.write_str("bool"),
+            UniformType::Mat3 => f.write_str("mat3"),
+            UniformType::Mat4 => f.write_str("mat4"),
+            UniformType::Sampler2D => f.write_str("sampler2D"),
+            UniformType::Other(kind) => write!(f, "GL type 0x{:04X}", kind),
+        }
+    }
+}
+
+//
+// An active uniform of a linked program. `size` is the array length, 1 for plain uniforms
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniformInfo {
+    pub location: GLint,
+    pub kind: UniformType,
+    pub size: i32,
+}
+
+//
+// Active uniforms of a program by name, queried once at link time. Arrays are listed under
+// their plain name, pointing at the first element, and under `name[i]` for every element
+//
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Uniforms(HashMap<String, UniformInfo>);
+
+impl Uniforms {
+    pub fn reflect(program: GLuint) -> Self {
+        let mut uniforms = HashMap::new();
+
+        unsafe {
+            let mut count = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+
+            let mut max_length = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+
+            for index in 0..count.max(0) as GLuint {
+                let mut buf = vec![0u8; max_length.max(1) as usize];
+                let (mut length, mut size, mut kind) = (0, 0, 0);
+
+                gl::GetActiveUniform(
+                    program,
+                    index,
+                    max_length,
+                    &mut length,
+                    &mut size,
+                    &mut kind,
+                    buf.as_mut_ptr() as *mut GLchar,
+                );
+                buf.truncate(length.max(0) as usize);
+
+                let name = String::from_utf8_lossy(&buf).into_owned();
+                let kind = UniformType::from_gl(kind);
+
+                // Arrays are reported as `name[0]`, block members have no location
+                let base = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+                let location = Self::location(program, &base);
+                if location < 0 {
+                    continue;
+                }
+
+                if size > 1 {
+                    for element in 1..size {
+                        let element_name = format!("{}[{}]", base, element);
+                        let info = UniformInfo {
+                            location: Self::location(program, &element_name),
+                            kind,
+                            size: size - element,
+                        };
+                        uniforms.insert(element_name, info);
+                    }
+                    uniforms.insert(format!("{}[0]", base), UniformInfo { location, kind, size });
+                }
+
+                uniforms.insert(base, UniformInfo { location, kind, size });
+            }
+        }
+
+        Uniforms(uniforms)
+    }
+
+    fn location(program: GLuint, name: &str) -> GLint {
+        match CString::new(name) {
+            Ok(name) => unsafe { gl::GetUniformLocation(program, name.as_ptr()) },
+            Err(_) => -1,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UniformInfo> {
+        self.0.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UniformInfo)> {
+        self.0.iter().map(|(name, info)| (name.as_str(), info))
+    }
+}
+
+//
+// A value that can be uploaded to a uniform, implemented for the scalar, vector and matrix
+// types and for slices of them, which fill consecutive array elements
+//
+pub trait Uniform {
+    // Whether a GLSL uniform of type `kind` can be set from this value
+    fn accepts(&self, kind: UniformType) -> bool;
+
+    // Array elements written
+    fn count(&self) -> usize {
+        1
+    }
+
+    // Needs the program bound
+    fn upload(&self, location: GLint);
+}
+
+macro_rules! impl_uniform {
+    ($type:ty, [$($kind:ident),+], |$location:ident, $count:ident, $data:ident| $upload:expr) => {
+        impl Uniform for $type {
+            fn accepts(&self, kind: UniformType) -> bool {
+                matches!(kind, $(UniformType::$kind)|+)
+            }
+
+            fn upload(&self, location: GLint) {
+                std::slice::from_ref(self).upload(location);
+            }
+        }
+
+        impl Uniform for [$type] {
+            fn accepts(&self, kind: UniformType) -> bool {
+                matches!(kind, $(UniformType::$kind)|+)
+            }
+
+            fn count(&self) -> usize {
+                self.len()
+            }
+
+            fn upload(&self, $location: GLint) {
+                let $count = self.len() as GLsizei;
+                let $data = self.as_ptr();
+
+                unsafe {
+                    $upload;
+                }
+            }
+        }
+    };
+}
+
+// Booleans are set through the integer calls, and a sampler takes its texture unit
+impl_uniform!(i32, [Int, Bool, Sampler2D], |location, count, data| gl::Uniform1iv(location, count, data));
+impl_uniform!(f32, [Float], |location, count, data| gl::Uniform1fv(location, count, data));
+impl_uniform!(Vec2<f32>, [Vec2], |location, count, data| gl::Uniform2fv(location, count, data as *const f32));
+impl_uniform!(Vec3<f32>, [Vec3], |location, count, data| gl::Uniform3fv(location, count, data as *const f32));
+impl_uniform!(Vec4<f32>, [Vec4], |location, count, data| gl::Uniform4fv(location, count, data as *const f32));
+impl_uniform!(Vec2<i32>, [IVec2], |location, count, data| gl::Uniform2iv(location, count, data as *const i32));
+impl_uniform!(Vec3<i32>, [IVec3], |location, count, data| gl::Uniform3iv(location, count, data as *const i32));
+impl_uniform!(Vec4<i32>, [IVec4], |location, count, data| gl::Uniform4iv(location, count, data as *const i32));
+// vek matrices are column major, as GL expects
+impl_uniform!(Mat3<f32>, [Mat3], |location, count, data| gl::UniformMatrix3fv(location, count, gl::FALSE, data as *const f32));
+impl_uniform!(Mat4<f32>, [Mat4], |location, count, data| gl::UniformMatrix4fv(location, count, gl::FALSE, data as *const f32));
+
+impl Uniform for bool {
+    fn accepts(&self, kind: UniformType) -> bool {
+        kind == UniformType::Bool
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl::Uniform1i(location, *self as GLint);
+        }
+    }
+}
+
+impl<U: Uniform, const N: usize> Uniform for [U; N]
+where
+    [U]: Uniform,
+{
+    fn accepts(&self, kind: UniformType) -> bool {
+        self[..].accepts(kind)
+    }
+
+    fn count(&self) -> usize {
+        N
+    }
+
+    fn upload(&self, location: GLint) {
+        self[..].upload(location);
+    }
+}
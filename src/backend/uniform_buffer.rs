@@ -0,0 +1,262 @@
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use vek::mat::{Mat3, Mat4};
+use vek::vec::{Vec2, Vec3, Vec4};
+
+use crate::backend::dialect::GlslVersion;
+use crate::backend::error::{check_gl_error, Result};
+use crate::backend::gl_gen::gl;
+use crate::backend::gl_gen::gl::types::*;
+use crate::backend::uniform::Uniform;
+
+//
+// A value with a std140 layout: its base alignment, and its bytes without trailing padding.
+// Arrays round the alignment and stride of their elements up to 16 bytes, matrices are
+// stored as arrays of column vectors
+//
+pub trait Std140 {
+    const ALIGN: usize;
+
+    fn write_std140(&self, out: &mut Vec<u8>);
+}
+
+fn write_floats(out: &mut Vec<u8>, floats: &[f32]) {
+    for float in floats {
+        out.extend_from_slice(&float.to_ne_bytes());
+    }
+}
+
+impl Std140 for f32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(out, &[*self]);
+    }
+}
+
+impl Std140 for i32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+// GLSL bools are 4 bytes in a block
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(*self as u32).to_ne_bytes());
+    }
+}
+
+impl Std140 for Vec2<f32> {
+    const ALIGN: usize = 8;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(out, &[self.x, self.y]);
+    }
+}
+
+// A vec3 is aligned like a vec4 but only 12 bytes long, a following float fills the gap
+impl Std140 for Vec3<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(out, &[self.x, self.y, self.z]);
+    }
+}
+
+impl Std140 for Vec4<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(out, &[self.x, self.y, self.z, self.w]);
+    }
+}
+
+impl Std140 for Mat3<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        for column in self.into_col_arrays().iter() {
+            write_floats(out, &[column[0], column[1], column[2], 0.0]);
+        }
+    }
+}
+
+impl Std140 for Mat4<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(out, &self.into_col_array());
+    }
+}
+
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        for element in self {
+            let start = out.len();
+            element.write_std140(out);
+            pad_to(out, start, 16);
+        }
+    }
+}
+
+// Pads `out` so the bytes written since `start` are a multiple of `align`
+fn pad_to(out: &mut Vec<u8>, start: usize, align: usize) {
+    let length = out.len() - start;
+    out.resize(start + length.div_ceil(align) * align, 0);
+}
+
+//
+// Receives the members of a uniform block in declaration order, see UniformBlock
+//
+pub trait BlockVisitor {
+    fn member<T: Std140 + Uniform>(&mut self, name: &str, value: &T);
+}
+
+//
+// A Rust struct mirroring a GLSL uniform block. `NAME` is the block name in GLSL and `visit`
+// hands every member to the visitor in the order the block declares them, e.g.
+//
+//     visitor.member("view", &self.view);
+//     visitor.member("projection", &self.projection);
+//
+pub trait UniformBlock {
+    const NAME: &'static str;
+
+    fn visit<V: BlockVisitor>(&self, visitor: &mut V);
+}
+
+//
+// Lays out a block as std140, keeping the offset of every member
+//
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Std140Writer {
+    data: Vec<u8>,
+    offsets: Vec<(String, usize)>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write<B: UniformBlock>(block: &B) -> Self {
+        let mut writer = Self::new();
+        block.visit(&mut writer);
+        writer
+    }
+
+    pub fn offsets(&self) -> &[(String, usize)] {
+        &self.offsets
+    }
+
+    // The block's bytes, padded to a multiple of 16 as the block size is
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        pad_to(&mut self.data, 0, 16);
+        self.data
+    }
+}
+
+impl BlockVisitor for Std140Writer {
+    fn member<T: Std140 + Uniform>(&mut self, name: &str, value: &T) {
+        pad_to(&mut self.data, 0, T::ALIGN);
+        self.offsets.push((name.to_string(), self.data.len()));
+        value.write_std140(&mut self.data);
+    }
+}
+
+// Binding point of every block name a UniformBuffer has been created for, by index
+static BINDINGS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+// Binding point reserved for a block name, see Shader, which binds its blocks at link time
+pub fn uniform_block_binding(name: &str) -> Option<GLuint> {
+    let bindings = BINDINGS.lock().unwrap_or_else(|e| e.into_inner());
+    bindings.iter().position(|&bound| bound == name).map(|index| index as GLuint)
+}
+
+fn reserve_binding(name: &'static str) -> GLuint {
+    let mut bindings = BINDINGS.lock().unwrap_or_else(|e| e.into_inner());
+
+    match bindings.iter().position(|&bound| bound == name) {
+        Some(index) => index as GLuint,
+        None => {
+            bindings.push(name);
+            (bindings.len() - 1) as GLuint
+        }
+    }
+}
+
+//
+// GL buffer holding one uniform block for every shader. Each block name gets its own binding
+// point and shaders linked after the buffer was created bind their block of that name to it,
+// so per-frame data is uploaded once instead of set on every shader. Create buffers before
+// the shaders using them.
+//
+// OpenGL 2.1 has no uniform buffers: there the block members are plain uniforms (see
+// GlslVersion::translate_line), nothing is uploaded and each shader is given the values with
+// Shader::set_uniform_block instead
+//
+pub struct UniformBuffer<B: UniformBlock> {
+    buffer: GLuint,
+    binding: GLuint,
+    block: PhantomData<B>,
+}
+
+#[allow(dead_code)]
+impl<B: UniformBlock> UniformBuffer<B> {
+    pub fn new() -> Result<Self> {
+        let binding = reserve_binding(B::NAME);
+        let mut buffer = 0;
+
+        if GlslVersion::current().supports_uniform_buffers() {
+            unsafe {
+                gl::GenBuffers(1, &mut buffer);
+            }
+            check_gl_error()?;
+        }
+
+        Ok(UniformBuffer {
+            buffer,
+            binding,
+            block: PhantomData,
+        })
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    // Uploads the whole block and binds it, once per frame or whenever it changes
+    pub fn update(&self, block: &B) {
+        if self.buffer == 0 {
+            return;
+        }
+
+        let data = Std140Writer::write(block).into_bytes();
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer);
+            // Respecifying the storage lets the driver hand out fresh memory instead of
+            // waiting for draws still reading the previous frame's data
+            gl::BufferData(gl::UNIFORM_BUFFER, data.len() as GLsizeiptr, data.as_ptr() as *const _, gl::DYNAMIC_DRAW);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.buffer);
+        }
+    }
+}
+
+impl<B: UniformBlock> Drop for UniformBuffer<B> {
+    fn drop(&mut self) {
+        if self.buffer != 0 {
+            unsafe {
+                gl::DeleteBuffers(1, &self.buffer);
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::mem;
+use std::slice;
+
+use vek::vec::{Vec2, Vec3, Vec4};
+
+use crate::backend::gl_gen::gl;
+use crate::backend::gl_gen::gl::types::*;
+
+pub use procedural_generation_derive::VertexLayout;
+
+//
+// Component type of a vertex attribute as it is stored in the buffer
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeType {
+    F32,
+    F16,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl AttributeType {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            AttributeType::F32 => gl::FLOAT,
+            AttributeType::F16 => gl::HALF_FLOAT,
+            AttributeType::U8 => gl::UNSIGNED_BYTE,
+            AttributeType::I8 => gl::BYTE,
+            AttributeType::U16 => gl::UNSIGNED_SHORT,
+            AttributeType::I16 => gl::SHORT,
+            AttributeType::U32 => gl::UNSIGNED_INT,
+            AttributeType::I32 => gl::INT,
+        }
+    }
+
+    // Float types cannot be normalized or read as integers
+    pub const fn is_float(self) -> bool {
+        matches!(self, AttributeType::F32 | AttributeType::F16)
+    }
+}
+
+//
+// A 16-bit IEEE 754 float, for attributes where half the precision is plenty, e.g. normals
+// or texture coordinates
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Half(u16);
+
+impl Half {
+    pub const fn from_bits(bits: u16) -> Self {
+        Half(bits)
+    }
+
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    // Rounds to the nearest half, ties to even. Values too large become infinity
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+
+        // Infinity stays infinity, NaN stays a (quiet) NaN
+        if exponent == 0xff {
+            let nan = if mantissa != 0 { 0x200 } else { 0 };
+            return Half(sign | 0x7c00 | nan);
+        }
+
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return Half(sign | 0x7c00);
+        }
+
+        // Too small for a normal half, shift the implicit bit into a subnormal mantissa
+        let (half, shift, mantissa) = if exponent <= 0 {
+            if exponent < -10 {
+                return Half(sign);
+            }
+
+            let mantissa = mantissa | 0x80_0000;
+            let shift = (14 - exponent) as u32;
+            (mantissa >> shift, shift, mantissa)
+        } else {
+            (((exponent as u32) << 10) | (mantissa >> 13), 13, mantissa)
+        };
+
+        let rest = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = rest > halfway || (rest == halfway && half & 1 == 1);
+
+        // A carry out of the mantissa correctly bumps the exponent, up to infinity
+        Half(sign | (half + round_up as u32) as u16)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x3ff) as u32;
+
+        match exponent {
+            0 => {
+                let value = mantissa as f32 * (-24f32).exp2();
+                if sign != 0 {
+                    -value
+                } else {
+                    value
+                }
+            }
+            0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+            _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+        }
+    }
+}
+
+impl From<f32> for Half {
+    fn from(value: f32) -> Self {
+        Half::from_f32(value)
+    }
+}
+
+impl From<Half> for f32 {
+    fn from(value: Half) -> Self {
+        value.to_f32()
+    }
+}
+
+//
+// A field type a vertex struct can hold: a scalar, or an array or vek vector of 1 to 4 of them
+//
+pub trait VertexField {
+    const KIND: AttributeType;
+    const COMPONENTS: usize;
+}
+
+//
+// A single component of a vertex attribute
+//
+pub trait VertexScalar: Copy {
+    const KIND: AttributeType;
+}
+
+macro_rules! impl_vertex_scalar {
+    ($($type:ty => $kind:ident),+) => {
+        $(
+            impl VertexScalar for $type {
+                const KIND: AttributeType = AttributeType::$kind;
+            }
+
+            impl VertexField for $type {
+                const KIND: AttributeType = AttributeType::$kind;
+                const COMPONENTS: usize = 1;
+            }
+        )+
+    };
+}
+
+impl_vertex_scalar!(f32 => F32, Half => F16, u8 => U8, i8 => I8, u16 => U16, i16 => I16, u32 => U32, i32 => I32);
+
+impl<S: VertexScalar, const N: usize> VertexField for [S; N] {
+    const KIND: AttributeType = S::KIND;
+    const COMPONENTS: usize = N;
+}
+
+// vek's default vectors are #[repr(C)], their components are laid out like an array
+impl<S: VertexScalar> VertexField for Vec2<S> {
+    const KIND: AttributeType = S::KIND;
+    const COMPONENTS: usize = 2;
+}
+
+impl<S: VertexScalar> VertexField for Vec3<S> {
+    const KIND: AttributeType = S::KIND;
+    const COMPONENTS: usize = 3;
+}
+
+impl<S: VertexScalar> VertexField for Vec4<S> {
+    const KIND: AttributeType = S::KIND;
+    const COMPONENTS: usize = 4;
+}
+
+//
+// One attribute of a vertex struct, i.e. one glVertexAttribPointer call. Integer components
+// are converted to floats by GL, mapped to 0..1 (-1..1 if signed) when `normalized`, unless
+// the attribute is `integer`, which keeps them integers for `ivec`/`uvec` shader inputs
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub components: usize,
+    pub kind: AttributeType,
+    pub normalized: bool,
+    pub integer: bool,
+    // Byte offset into the vertex
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    pub fn of<T: VertexField>(location: u32, offset: usize, normalized: bool, integer: bool) -> Self {
+        assert!((1..=4).contains(&T::COMPONENTS), "vertex attributes have 1 to 4 components");
+        assert!(
+            !(T::KIND.is_float() && (normalized || integer)),
+            "float vertex attributes cannot be normalized or integer"
+        );
+        assert!(!(normalized && integer), "a vertex attribute cannot be both normalized and integer");
+
+        VertexAttribute {
+            location,
+            components: T::COMPONENTS,
+            kind: T::KIND,
+            normalized,
+            integer,
+            offset,
+        }
+    }
+}
+
+//
+// A vertex struct that describes its own attributes, so a slice of them can be uploaded as
+// it is. Derive it on a #[repr(C)] struct:
+//
+//     #[derive(Clone, Copy, VertexLayout)]
+//     #[repr(C)]
+//     struct Vertex {
+//         position: Vec3<f32>,
+//         #[vertex(normalized)]
+//         color: [u8; 4],
+//         #[vertex(location = 3, integer)]
+//         material: u32,
+//     }
+//
+// Fields are attributes at the location of their position in the struct unless given one.
+// Implementing it by hand is unsafe: the struct must have no padding, as its bytes are read
+// directly, and the attributes must lie within it
+//
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait VertexLayout: Copy + 'static {
+    fn attributes() -> Vec<VertexAttribute>;
+
+    fn stride() -> usize {
+        mem::size_of::<Self>()
+    }
+}
+
+//
+// Vertices of any layout as the bytes uploaded to an ArrayBuffer, so drawables with different
+// vertex structs can be handed to Object alike
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vertices {
+    data: Vec<u8>,
+    stride: usize,
+    attributes: Vec<VertexAttribute>,
+}
+
+impl Vertices {
+    pub fn new<T: VertexLayout>(vertices: &[T]) -> Self {
+        Vertices {
+            data: as_bytes(vertices).to_vec(),
+            stride: T::stride(),
+            attributes: T::attributes(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len().checked_div(self.stride).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+pub fn as_bytes<T: VertexLayout>(vertices: &[T]) -> &[u8] {
+    // VertexLayout guarantees there is no padding, so every byte is initialised
+    unsafe { slice::from_raw_parts(vertices.as_ptr() as *const u8, mem::size_of_val(vertices)) }
+}
@@ -1,13 +1,22 @@
-use crate::backend::drawable::*;
+use vek::vec::Vec3;
 
-use std::mem;
+use crate::backend::drawable::*;
+use crate::backend::vertex_layout::{VertexLayout, Vertices};
+use crate::mesh::aabb::Aabb;
 
 pub struct Cube;
 
+#[derive(Clone, Copy, Debug, VertexLayout)]
+#[repr(C)]
+struct CubeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
 impl Drawable for Cube {
     fn vertex_attributes(&self) -> DrawableAttributes {
         #[rustfmt::skip]
-        let vertex_attributes = [
+        let vertex_attributes: [f32; 216] = [
             -0.5, -0.5, -0.5,
              0.0,  0.0, -1.0,
              0.5, -0.5, -0.5,
@@ -80,31 +89,23 @@ impl Drawable for Cube {
              0.0,  1.0,  0.0,
             -0.5,  0.5, -0.5,
              0.0,  1.0,  0.0,
-        ]
-        .to_vec();
-
-        let vertex_attribute_pointers = vec![
-            VertexAttribPointer {
-                index: 0,
-                size: 3,
-                stride: 6 * mem::size_of::<f32>(),
-                offset: 0,
-            },
-            VertexAttribPointer {
-                index: 1,
-                size: 3,
-                stride: 6 * mem::size_of::<f32>(),
-                offset: 3 * mem::size_of::<f32>() as usize,
-            },
         ];
 
+        let vertices = vertex_attributes
+            .chunks_exact(6)
+            .map(|vertex| CubeVertex {
+                position: [vertex[0], vertex[1], vertex[2]],
+                normal: [vertex[3], vertex[4], vertex[5]],
+            })
+            .collect::<Vec<_>>();
+
         DrawableAttributes {
             buffer: Buffer::ArrayBuffer {
-                vertex_attributes,
-                vertex_attribute_pointers,
+                vertices: Vertices::new(&vertices),
             },
-            draw_count: 132,
+            draw_count: vertices.len(),
             draw_primitive: DrawPrimitive::TRIANGLES,
+            bounds: Some(Aabb::new(Vec3::broadcast(-0.5), Vec3::broadcast(0.5))),
         }
     }
 }
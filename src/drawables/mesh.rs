@@ -0,0 +1,112 @@
+use std::ops::Range;
+
+use vek::vec::{Vec2, Vec3};
+
+use crate::backend::dialect::GlslVersion;
+use crate::backend::drawable::*;
+use crate::backend::vertex_layout::{VertexLayout, Vertices};
+use crate::generation::{LodPatch, Terrain, TerrainChunk};
+use crate::mesh::{Topology, TriangleMesh, PRIMITIVE_RESTART_INDEX};
+
+// Interleaved vertex matching the shader layout locations 0, 1 and 2
+#[derive(Clone, Copy, Debug, VertexLayout)]
+#[repr(C)]
+struct MeshVertex {
+    position: Vec3<f32>,
+    normal: Vec3<f32>,
+    texture_uv: Vec2<f32>,
+}
+
+// A MeshVertex and the height it morphs into on the next coarser LOD level, at location 3
+#[derive(Clone, Copy, Debug, VertexLayout)]
+#[repr(C)]
+struct LodVertex {
+    position: Vec3<f32>,
+    normal: Vec3<f32>,
+    texture_uv: Vec2<f32>,
+    morph_height: f32,
+}
+
+//
+// The vertices in `range` of a mesh, laid out as its Drawable uploads them, to update part of
+// an Object built from the mesh with Object::update_vertices. Missing normals or texture
+// coordinates are filled with zeroes
+//
+pub fn mesh_vertices(mesh: &TriangleMesh, range: Range<usize>) -> Vertices {
+    let vertices = range
+        .map(|i| MeshVertex {
+            position: mesh.positions[i],
+            normal: mesh.normals.get(i).copied().unwrap_or_default(),
+            texture_uv: mesh.texture_uvs.get(i).copied().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+
+    Vertices::new(&vertices)
+}
+
+//
+// Uploads a CPU-side mesh as interleaved position, normal and texture coordinate attributes
+//
+impl Drawable for TriangleMesh {
+    fn vertex_attributes(&self) -> DrawableAttributes {
+        indexed_attributes(self, mesh_vertices(self, 0..self.vertex_count()))
+    }
+}
+
+// Draws `vertices`, one for each vertex of the mesh, with the mesh's indices
+fn indexed_attributes(mesh: &TriangleMesh, vertices: Vertices) -> DrawableAttributes {
+    // OpenGL 2.1 cannot restart strips, draw their triangles one by one instead
+    let restarts = mesh.topology == Topology::TriangleStrip && mesh.indices.contains(&PRIMITIVE_RESTART_INDEX);
+    if restarts && !GlslVersion::current().supports_primitive_restart() {
+        return indexed_attributes(&mesh.to_triangle_list(), vertices);
+    }
+
+    let draw_primitive = match mesh.topology {
+        Topology::Triangles => DrawPrimitive::TRIANGLES,
+        Topology::TriangleStrip => DrawPrimitive::TRIANGLE_STRIP,
+    };
+
+    DrawableAttributes {
+        buffer: Buffer::IndexBuffer {
+            vertices,
+            indices: mesh.indices.clone(),
+        },
+        draw_count: mesh.indices.len(),
+        draw_primitive,
+        bounds: mesh.bounds(),
+    }
+}
+
+impl Drawable for Terrain {
+    fn vertex_attributes(&self) -> DrawableAttributes {
+        self.mesh().vertex_attributes()
+    }
+}
+
+// Built in chunk-local coordinates, draw it with the chunk's origin as the translation
+impl Drawable for TerrainChunk {
+    fn vertex_attributes(&self) -> DrawableAttributes {
+        self.mesh().vertex_attributes()
+    }
+}
+
+// Needs the terrain shader built with LOD defined to morph, see LodTerrain
+impl Drawable for LodPatch {
+    fn vertex_attributes(&self) -> DrawableAttributes {
+        let mesh = self.mesh();
+        let vertices = (0..mesh.vertex_count())
+            .map(|i| LodVertex {
+                position: mesh.positions[i],
+                normal: mesh.normals[i],
+                texture_uv: mesh.texture_uvs[i],
+                morph_height: self.morph_heights()[i],
+            })
+            .collect::<Vec<_>>();
+
+        // Morphing moves the vertices up and down, the box has to hold both heights
+        DrawableAttributes {
+            bounds: Some(self.bounds().translated(-self.origin())),
+            ..indexed_attributes(mesh, Vertices::new(&vertices))
+        }
+    }
+}
@@ -1,6 +1,5 @@
-#[allow(dead_code)]
 mod cube;
+pub use cube::Cube;
 
-#[allow(dead_code)]
-mod terrain;
-pub use terrain::Terrain;
+mod mesh;
+pub use mesh::mesh_vertices;
@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use vek::vec::{Vec2, Vec3};
+
+use crate::generation::height_source::{HeightSource, NoiseParams, PerlinSource};
+use crate::generation::heightfield::Heightfield;
+use crate::generation::terrain::{Terrain, DEFAULT_CELL_SPACING, DEFAULT_GRID_SIZE};
+use crate::mesh::aabb::Aabb;
+use crate::mesh::grid::GridMeshBuilder;
+use crate::mesh::triangle_mesh::{Topology, TriangleMesh};
+
+//
+// Cells along each side of a chunk, so chunks have one more vertex than that per side
+//
+pub const DEFAULT_CHUNK_SIZE: u32 = 64;
+
+//
+// Position of a chunk in the grid of chunks, chunk (0, 0) starting at the world origin
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkCoord {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+}
+
+//
+// A piece of terrain meshed on its own. Its mesh is in chunk-local coordinates with `origin`
+// the world position of its first vertex, so far away chunks keep their precision. Chunks
+// next to each other share the vertices along their common edge, read from the same global
+// grid position, so their edges meet without cracks
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerrainChunk {
+    coord: ChunkCoord,
+    origin: Vec3<f32>,
+    mesh: TriangleMesh,
+}
+
+impl TerrainChunk {
+    //
+    // Builds the chunk over `width` x `depth` vertices starting at global grid vertex `first`.
+    // `height` gives the height of any global grid vertex and is also asked for the ring of
+    // vertices around the chunk, so the normals along its edges take the neighbouring
+    // triangles into account and match the neighbouring chunks
+    //
+    pub fn build<H: Fn(i64, i64) -> f32>(
+        coord: ChunkCoord,
+        first: (i64, i64),
+        width: u32,
+        depth: u32,
+        spacing: f32,
+        height: H,
+    ) -> Self {
+        let (first_x, first_z) = first;
+
+        // Heights with a one vertex border, as a grid of its own to compute the normals on
+        let bordered = GridMeshBuilder::new(width + 2, depth + 2).with_topology(Topology::Triangles);
+        let mut positions = Vec::with_capacity(((width + 2) * (depth + 2)) as usize);
+        for x in -1..=width as i64 {
+            for z in -1..=depth as i64 {
+                let y = height(first_x + x, first_z + z);
+                positions.push(Vec3::new(x as f32 * spacing, y, z as f32 * spacing));
+            }
+        }
+        let bordered = bordered.build(positions, Vec::new());
+
+        let grid = GridMeshBuilder::new(width, depth);
+        let mut mesh = TriangleMesh {
+            positions: Vec::with_capacity((width * depth) as usize),
+            normals: Vec::with_capacity((width * depth) as usize),
+            texture_uvs: Vec::with_capacity((width * depth) as usize),
+            indices: grid.indices(),
+            topology: grid.topology(),
+        };
+
+        for x in 0..width {
+            for z in 0..depth {
+                let inner = ((x + 1) * (depth + 2) + z + 1) as usize;
+                mesh.positions.push(bordered.positions[inner]);
+                mesh.normals.push(bordered.normals[inner]);
+                // The texture repeats once per cell, so local coordinates line up with the
+                // neighbours and stay small
+                mesh.texture_uvs.push(Vec2::new(x as f32, z as f32));
+            }
+        }
+
+        TerrainChunk {
+            coord,
+            origin: Vec3::new(first_x as f32 * spacing, 0.0, first_z as f32 * spacing),
+            mesh,
+        }
+    }
+
+    pub fn coord(&self) -> ChunkCoord {
+        self.coord
+    }
+
+    pub fn origin(&self) -> Vec3<f32> {
+        self.origin
+    }
+
+    pub fn mesh(&self) -> &TriangleMesh {
+        &self.mesh
+    }
+
+    // World-space box around the chunk
+    pub fn bounds(&self) -> Aabb {
+        let bounds = self.mesh.bounds().expect("Terrain chunks have at least one vertex");
+        bounds.translated(self.origin)
+    }
+
+    pub fn into_mesh(self) -> TriangleMesh {
+        self.mesh
+    }
+}
+
+//
+// Terrain without an edge, built chunk by chunk from a height source sampled at global grid
+// positions. `noise_scale` grid vertices make up one unit of the source's coordinates, offset
+// the way Heightfield::generate offsets them, so the vertices of a Terrain `noise_scale`
+// vertices wide on its longest side have the same heights here. The default matches a
+// default sized Terrain
+//
+#[derive(Clone)]
+pub struct ChunkedTerrain {
+    source: Arc<dyn HeightSource>,
+    chunk_size: u32,
+    spacing: f32,
+    noise_scale: f64,
+}
+
+impl ChunkedTerrain {
+    pub fn new<S: HeightSource + 'static>(source: S, chunk_size: u32, spacing: f32) -> Self {
+        assert!(chunk_size >= 1, "Chunks need at least one cell");
+        assert!(spacing > 0.0, "Terrain cell spacing must be positive, got {}", spacing);
+
+        Self {
+            source: Arc::new(source),
+            chunk_size,
+            spacing,
+            noise_scale: DEFAULT_GRID_SIZE as f64,
+        }
+    }
+
+    pub fn with_noise_scale(mut self, noise_scale: f64) -> Self {
+        self.noise_scale = noise_scale;
+        self
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    // World-space length of a chunk side
+    pub fn chunk_extent(&self) -> f32 {
+        self.chunk_size as f32 * self.spacing
+    }
+
+    // Chunk containing the world-space position `x`, `z`
+    pub fn chunk_at(&self, x: f32, z: f32) -> ChunkCoord {
+        let extent = self.chunk_extent();
+        ChunkCoord::new((x / extent).floor() as i32, (z / extent).floor() as i32)
+    }
+
+    // Height of the global grid vertex `x`, `z`
+    pub fn height(&self, x: i64, z: i64) -> f32 {
+        self.source.height(x as f64 / self.noise_scale - 0.5, z as f64 / self.noise_scale - 0.5)
+    }
+
+    pub fn build_chunk(&self, coord: ChunkCoord) -> TerrainChunk {
+        let size = self.chunk_size as i64;
+        let first = (coord.x as i64 * size, coord.z as i64 * size);
+
+        TerrainChunk::build(
+            coord,
+            first,
+            self.chunk_size + 1,
+            self.chunk_size + 1,
+            self.spacing,
+            |x, z| self.height(x, z),
+        )
+    }
+}
+
+impl Default for ChunkedTerrain {
+    fn default() -> Self {
+        Self::new(
+            PerlinSource::new(NoiseParams::default()),
+            DEFAULT_CHUNK_SIZE,
+            DEFAULT_CELL_SPACING,
+        )
+    }
+}
+
+impl Terrain {
+    //
+    // Splits the terrain into chunks of up to `chunk_size` cells per side, row by row along
+    // Z. The chunks along the far edges are smaller when the grid does not divide evenly
+    //
+    pub fn chunks(&self, chunk_size: u32) -> Vec<TerrainChunk> {
+        let heightfield = self.heightfield();
+        let (columns, rows) = self.chunk_count(chunk_size);
+
+        (0..columns)
+            .flat_map(|x| (0..rows).map(move |z| ChunkCoord::new(x as i32, z as i32)))
+            .map(|coord| self.build_chunk(&heightfield, coord, chunk_size))
+            .collect()
+    }
+
+    // Chunks along X and Z
+    pub fn chunk_count(&self, chunk_size: u32) -> (u32, u32) {
+        assert!(chunk_size >= 1, "Chunks need at least one cell");
+        ((self.width() - 1).div_ceil(chunk_size), (self.depth() - 1).div_ceil(chunk_size))
+    }
+
+    //
+    // Builds one chunk from the terrain's heightfield, e.g. to rebuild only the chunk an edit
+    // touched. Heights beyond the edge of the grid repeat the edge. Panics if `coord` is not
+    // one of the chunks `chunk_count` gives
+    //
+    pub fn build_chunk(&self, heightfield: &Heightfield, coord: ChunkCoord, chunk_size: u32) -> TerrainChunk {
+        let (columns, rows) = self.chunk_count(chunk_size);
+        assert!(
+            coord.x >= 0 && coord.z >= 0 && (coord.x as u32) < columns && (coord.z as u32) < rows,
+            "Chunk ({}, {}) is outside the {}x{} chunk grid",
+            coord.x,
+            coord.z,
+            columns,
+            rows
+        );
+
+        let first_x = coord.x as u32 * chunk_size;
+        let first_z = coord.z as u32 * chunk_size;
+        let width = chunk_size.min(self.width() - 1 - first_x) + 1;
+        let depth = chunk_size.min(self.depth() - 1 - first_z) + 1;
+
+        let (last_x, last_z) = (heightfield.width() as i64 - 1, heightfield.depth() as i64 - 1);
+
+        TerrainChunk::build(
+            coord,
+            (first_x as i64, first_z as i64),
+            width,
+            depth,
+            self.spacing(),
+            |x, z| heightfield.get(x.clamp(0, last_x) as u32, z.clamp(0, last_z) as u32),
+        )
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use png::{BitDepth, ColorType, Encoder};
+use vek::vec::Vec3;
+
+use crate::generation::heightfield::Heightfield;
+use crate::generation::import::{Endianness, HeightmapFormat, HeightmapInfo, RawFormat};
+
+//
+// Heightmap exporters, the counterparts of the importers. Heights are normalized over the
+// heightfield's own range for the most precision, and that range is written to a sidecar
+// next to the file so importing it again reproduces the same terrain
+//
+
+// Writes a 16-bit grayscale PNG
+pub fn export_png<P: AsRef<Path>>(heightfield: &Heightfield, path: P) -> io::Result<HeightmapInfo> {
+    let info = info(heightfield, HeightmapFormat::Png);
+
+    let data: Vec<u8> = samples_u16(heightfield, &info)
+        .flat_map(|sample| sample.to_be_bytes())
+        .collect();
+
+    write_png(&path, info.width, info.depth, ColorType::Grayscale, BitDepth::Sixteen, &data)?;
+
+    info.save(path)?;
+    Ok(info)
+}
+
+// Writes a headerless RAW heightmap. Little-endian R16 is the format Unity and Unreal
+// landscape importers expect
+pub fn export_raw<P: AsRef<Path>>(
+    heightfield: &Heightfield,
+    path: P,
+    format: RawFormat,
+    endianness: Endianness,
+) -> io::Result<HeightmapInfo> {
+    let info = info(heightfield, HeightmapFormat::Raw { format, endianness });
+
+    let data: Vec<u8> = match (format, endianness) {
+        (RawFormat::R16, Endianness::Little) => samples_u16(heightfield, &info)
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect(),
+        (RawFormat::R16, Endianness::Big) => samples_u16(heightfield, &info)
+            .flat_map(|sample| sample.to_be_bytes())
+            .collect(),
+        (RawFormat::R32, Endianness::Little) => samples(heightfield, &info)
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect(),
+        (RawFormat::R32, Endianness::Big) => samples(heightfield, &info)
+            .flat_map(|sample| sample.to_be_bytes())
+            .collect(),
+    };
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    writer.write_all(&data)?;
+    writer.flush()?;
+
+    info.save(path)?;
+    Ok(info)
+}
+
+// Writes an 8-bit RGB PNG of the surface normals, `spacing` being the distance in world units
+// between neighbouring samples. Red holds X, green Z and blue the up axis, each mapped from
+// -1..1 onto 0..255
+pub fn export_normal_map<P: AsRef<Path>>(heightfield: &Heightfield, spacing: f32, path: P) -> io::Result<()> {
+    let data: Vec<u8> = rows(heightfield)
+        .flat_map(|(x, z)| {
+            let (dx, dz) = gradient(heightfield, spacing, x, z);
+            let normal = Vec3::new(-dx, 1.0, -dz).normalized();
+
+            [normal.x, normal.z, normal.y].map(|n| ((n * 0.5 + 0.5) * u8::MAX as f32).round() as u8)
+        })
+        .collect();
+
+    write_png(path, heightfield.width(), heightfield.depth(), ColorType::RGB, BitDepth::Eight, &data)
+}
+
+// Writes an 8-bit grayscale PNG of the slope angle, flat ground is black and vertical cliffs
+// are white
+pub fn export_slope_map<P: AsRef<Path>>(heightfield: &Heightfield, spacing: f32, path: P) -> io::Result<()> {
+    let data: Vec<u8> = rows(heightfield)
+        .map(|(x, z)| {
+            let (dx, dz) = gradient(heightfield, spacing, x, z);
+            let angle = (dx * dx + dz * dz).sqrt().atan() / std::f32::consts::FRAC_PI_2;
+
+            (angle * u8::MAX as f32).round() as u8
+        })
+        .collect();
+
+    write_png(path, heightfield.width(), heightfield.depth(), ColorType::Grayscale, BitDepth::Eight, &data)
+}
+
+fn write_png<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    depth: u32,
+    color: ColorType,
+    bit_depth: BitDepth,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), width, depth);
+    encoder.set_color(color);
+    encoder.set_depth(bit_depth);
+    encoder.write_header()?.write_image_data(data)?;
+
+    Ok(())
+}
+
+// Sample coordinates in file order, one row of `width` samples per Z
+fn rows(heightfield: &Heightfield) -> impl Iterator<Item = (u32, u32)> {
+    let (width, depth) = (heightfield.width(), heightfield.depth());
+    (0..depth).flat_map(move |z| (0..width).map(move |x| (x, z)))
+}
+
+// Height change per world unit along X and Z, from central differences inside the grid and
+// one-sided ones along its edges
+fn gradient(heightfield: &Heightfield, spacing: f32, x: u32, z: u32) -> (f32, f32) {
+    let (x0, x1) = (x.saturating_sub(1), (x + 1).min(heightfield.width() - 1));
+    let (z0, z1) = (z.saturating_sub(1), (z + 1).min(heightfield.depth() - 1));
+
+    (
+        (heightfield.get(x1, z) - heightfield.get(x0, z)) / ((x1 - x0) as f32 * spacing),
+        (heightfield.get(x, z1) - heightfield.get(x, z0)) / ((z1 - z0) as f32 * spacing),
+    )
+}
+
+fn info(heightfield: &Heightfield, format: HeightmapFormat) -> HeightmapInfo {
+    HeightmapInfo {
+        width: heightfield.width(),
+        depth: heightfield.depth(),
+        format,
+        range: heightfield.range(),
+    }
+}
+
+// Normalized samples, one row of `width` samples per Z
+fn samples<'a>(heightfield: &'a Heightfield, info: &'a HeightmapInfo) -> impl Iterator<Item = f32> + 'a {
+    rows(heightfield).map(move |(x, z)| info.range.normalize(heightfield.get(x, z)))
+}
+
+fn samples_u16<'a>(heightfield: &'a Heightfield, info: &'a HeightmapInfo) -> impl Iterator<Item = u16> + 'a {
+    samples(heightfield, info).map(|sample| (sample.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::generation::height_source::{NoiseParams, PerlinSource};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("procedural_generation_{}_{}", std::process::id(), name))
+    }
+
+    fn heightfield() -> Heightfield {
+        Heightfield::generate(33, 21, &PerlinSource::new(NoiseParams::default()))
+    }
+
+    // Exports, re-imports through the sidecar and returns the largest height difference
+    fn round_trip<F>(name: &str, export: F) -> f32
+    where
+        F: FnOnce(&Heightfield, &PathBuf) -> io::Result<HeightmapInfo>,
+    {
+        let path = temp_path(name);
+        let original = heightfield();
+
+        let info = export(&original, &path).unwrap();
+        assert_eq!((info.width, info.depth), (33, 21));
+        assert_eq!(info.range, original.range());
+
+        let imported = HeightmapInfo::load(&path).unwrap().unwrap().import(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(HeightmapInfo::sidecar_path(&path)).unwrap();
+
+        assert_eq!((imported.width(), imported.depth()), (33, 21));
+        original
+            .heights()
+            .iter()
+            .zip(imported.heights())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn png_round_trips_at_16_bits() {
+        let error = round_trip("round_trip.png", |h, path| export_png(h, path));
+
+        // One 16-bit step of the 60 unit range is about 1e-3
+        assert!(error < 1e-3, "error {}", error);
+    }
+
+    #[test]
+    fn r16_round_trips_in_both_byte_orders() {
+        for &endianness in &[Endianness::Little, Endianness::Big] {
+            let error = round_trip("round_trip.r16", |h, path| export_raw(h, path, RawFormat::R16, endianness));
+
+            assert!(error < 1e-3, "{:?} error {}", endianness, error);
+        }
+    }
+
+    #[test]
+    fn r32_round_trips() {
+        let error = round_trip("round_trip.r32", |h, path| export_raw(h, path, RawFormat::R32, Endianness::Little));
+
+        assert!(error < 1e-4, "error {}", error);
+    }
+
+    #[test]
+    fn missing_sidecar_is_not_an_error() {
+        assert_eq!(HeightmapInfo::load(temp_path("no_such_heightmap.png")).unwrap(), None);
+    }
+}
@@ -0,0 +1,509 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::generation::height_source::{HeightSource, NoiseKind, NoiseParams};
+
+//
+// Serializable description of a heightfield recipe. Leaves are noise sources, inner nodes
+// combine or reshape the heights of their children. A recipe is turned into something the
+// terrain can sample with `build`, e.g. ridged mountains masked by low-frequency continents:
+//
+//   { "type": "select",
+//     "mask": { "type": "noise", "kind": "perlin", "params": { "octaves": 2, "frequency": 1.0 } },
+//     "low": { "type": "constant", "value": -10.0 },
+//     "high": { "type": "noise", "kind": "ridged_multi", "params": { "seed": 1 } },
+//     "threshold": 0.0, "falloff": 5.0 }
+//
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeightNode {
+    Noise {
+        kind: NoiseKind,
+        #[serde(default)]
+        params: NoiseParams,
+    },
+    Constant {
+        value: f32,
+    },
+    Add {
+        sources: Vec<HeightNode>,
+    },
+    Multiply {
+        sources: Vec<HeightNode>,
+    },
+    Min {
+        sources: Vec<HeightNode>,
+    },
+    Max {
+        sources: Vec<HeightNode>,
+    },
+    // `source * scale + bias`
+    ScaleBias {
+        source: Box<HeightNode>,
+        scale: f32,
+        bias: f32,
+    },
+    // Picks `low` where `mask` is below `threshold` and `high` above it, blending smoothly
+    // over `falloff` on either side
+    Select {
+        mask: Box<HeightNode>,
+        low: Box<HeightNode>,
+        high: Box<HeightNode>,
+        threshold: f32,
+        #[serde(default)]
+        falloff: f32,
+    },
+    // Remaps heights through a piecewise linear curve of `[input, output]` points
+    Curve {
+        source: Box<HeightNode>,
+        points: Vec<[f32; 2]>,
+    },
+    Clamp {
+        source: Box<HeightNode>,
+        min: f32,
+        max: f32,
+    },
+    // Flattens heights towards the nearest step below, forming plateaus between `steps`
+    Terrace {
+        source: Box<HeightNode>,
+        steps: Vec<f32>,
+    },
+    // Offsets the sample position of `source` by the heights of `warp_x` and `warp_z`
+    // multiplied by `strength`
+    Warp {
+        source: Box<HeightNode>,
+        warp_x: Box<HeightNode>,
+        warp_z: Box<HeightNode>,
+        strength: f64,
+    },
+}
+
+impl HeightNode {
+    // Reads a recipe, rejecting ones that parse but could not be built, see `validate`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let node: Self = serde_json::from_reader(reader)?;
+
+        node.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(node)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    //
+    // Checks the recipe can be built: curves need a point, terraces two steps, and min and
+    // max a source, as they would otherwise produce infinite heights
+    //
+    pub fn validate(&self) -> Result<(), String> {
+        let validate_all = |sources: &[HeightNode]| sources.iter().try_for_each(HeightNode::validate);
+
+        match self {
+            HeightNode::Noise { .. } | HeightNode::Constant { .. } => Ok(()),
+            HeightNode::Add { sources } | HeightNode::Multiply { sources } => validate_all(sources),
+            HeightNode::Min { sources } if sources.is_empty() => Err("Min needs at least one source".to_string()),
+            HeightNode::Max { sources } if sources.is_empty() => Err("Max needs at least one source".to_string()),
+            HeightNode::Min { sources } | HeightNode::Max { sources } => validate_all(sources),
+            HeightNode::Curve { points, .. } if points.is_empty() => Err("Curve needs at least one point".to_string()),
+            HeightNode::Terrace { steps, .. } if steps.len() < 2 => Err("Terrace needs at least two steps".to_string()),
+            HeightNode::ScaleBias { source, .. }
+            | HeightNode::Curve { source, .. }
+            | HeightNode::Clamp { source, .. }
+            | HeightNode::Terrace { source, .. } => source.validate(),
+            HeightNode::Select { mask, low, high, .. } => {
+                mask.validate()?;
+                low.validate()?;
+                high.validate()
+            }
+            HeightNode::Warp {
+                source,
+                warp_x,
+                warp_z,
+                ..
+            } => {
+                source.validate()?;
+                warp_x.validate()?;
+                warp_z.validate()
+            }
+        }
+    }
+
+    //
+    // Instantiates the recipe, panicking if it is not valid. `seed` is added to the seed of
+    // every noise leaf so a single recipe can produce many different maps
+    //
+    pub fn build(&self, seed: u32) -> Box<dyn HeightSource> {
+        if let Err(e) = self.validate() {
+            panic!("Invalid height recipe: {}", e);
+        }
+
+        self.build_valid(seed)
+    }
+
+    fn build_valid(&self, seed: u32) -> Box<dyn HeightSource> {
+        let build_all = |sources: &[HeightNode]| -> Vec<Box<dyn HeightSource>> {
+            sources.iter().map(|source| source.build_valid(seed)).collect()
+        };
+
+        match self {
+            HeightNode::Noise { kind, params } => kind.source(NoiseParams {
+                seed: params.seed.wrapping_add(seed),
+                ..*params
+            }),
+            HeightNode::Constant { value } => Box::new(Constant(*value)),
+            HeightNode::Add { sources } => Box::new(Combine {
+                sources: build_all(sources),
+                identity: 0.0,
+                op: |a, b| a + b,
+            }),
+            HeightNode::Multiply { sources } => Box::new(Combine {
+                sources: build_all(sources),
+                identity: 1.0,
+                op: |a, b| a * b,
+            }),
+            HeightNode::Min { sources } => Box::new(Combine {
+                sources: build_all(sources),
+                identity: f32::INFINITY,
+                op: f32::min,
+            }),
+            HeightNode::Max { sources } => Box::new(Combine {
+                sources: build_all(sources),
+                identity: f32::NEG_INFINITY,
+                op: f32::max,
+            }),
+            HeightNode::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => Box::new(ScaleBias {
+                source: source.build_valid(seed),
+                scale: *scale,
+                bias: *bias,
+            }),
+            HeightNode::Select {
+                mask,
+                low,
+                high,
+                threshold,
+                falloff,
+            } => Box::new(Select {
+                mask: mask.build_valid(seed),
+                low: low.build_valid(seed),
+                high: high.build_valid(seed),
+                threshold: *threshold,
+                falloff: falloff.abs(),
+            }),
+            HeightNode::Curve { source, points } => {
+                let mut points = points.clone();
+                points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+
+                Box::new(Curve {
+                    source: source.build_valid(seed),
+                    points,
+                })
+            }
+            HeightNode::Clamp { source, min, max } => Box::new(Clamp {
+                source: source.build_valid(seed),
+                min: *min,
+                max: *max,
+            }),
+            HeightNode::Terrace { source, steps } => {
+                let mut steps = steps.clone();
+                steps.sort_by(f32::total_cmp);
+
+                Box::new(Terrace {
+                    source: source.build_valid(seed),
+                    steps,
+                })
+            }
+            HeightNode::Warp {
+                source,
+                warp_x,
+                warp_z,
+                strength,
+            } => Box::new(Warp {
+                source: source.build_valid(seed),
+                warp_x: warp_x.build_valid(seed),
+                warp_z: warp_z.build_valid(seed),
+                strength: *strength,
+            }),
+        }
+    }
+}
+
+struct Constant(f32);
+
+impl HeightSource for Constant {
+    fn height(&self, _x: f64, _z: f64) -> f32 {
+        self.0
+    }
+}
+
+struct Combine {
+    sources: Vec<Box<dyn HeightSource>>,
+    identity: f32,
+    op: fn(f32, f32) -> f32,
+}
+
+impl HeightSource for Combine {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        self.sources
+            .iter()
+            .fold(self.identity, |acc, source| (self.op)(acc, source.height(x, z)))
+    }
+}
+
+struct ScaleBias {
+    source: Box<dyn HeightSource>,
+    scale: f32,
+    bias: f32,
+}
+
+impl HeightSource for ScaleBias {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        self.source.height(x, z) * self.scale + self.bias
+    }
+}
+
+struct Select {
+    mask: Box<dyn HeightSource>,
+    low: Box<dyn HeightSource>,
+    high: Box<dyn HeightSource>,
+    threshold: f32,
+    falloff: f32,
+}
+
+impl HeightSource for Select {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        let mask = self.mask.height(x, z);
+
+        if mask <= self.threshold - self.falloff {
+            return self.low.height(x, z);
+        }
+        if mask >= self.threshold + self.falloff {
+            return self.high.height(x, z);
+        }
+
+        // Only reachable with a non-zero falloff
+        let t = (mask - (self.threshold - self.falloff)) / (2.0 * self.falloff);
+        let t = t * t * (3.0 - 2.0 * t);
+
+        let low = self.low.height(x, z);
+        let high = self.high.height(x, z);
+        low + (high - low) * t
+    }
+}
+
+struct Curve {
+    source: Box<dyn HeightSource>,
+    points: Vec<[f32; 2]>,
+}
+
+impl HeightSource for Curve {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        let value = self.source.height(x, z);
+
+        // NaN, e.g. from degenerate scales upstream, counts as below the first point
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if value.is_nan() || value <= first[0] {
+            return first[1];
+        }
+        if value >= last[0] {
+            return last[1];
+        }
+
+        let upper = self.points.partition_point(|p| p[0] <= value);
+        let (a, b) = (self.points[upper - 1], self.points[upper]);
+        let t = (value - a[0]) / (b[0] - a[0]);
+
+        a[1] + (b[1] - a[1]) * t
+    }
+}
+
+struct Clamp {
+    source: Box<dyn HeightSource>,
+    min: f32,
+    max: f32,
+}
+
+impl HeightSource for Clamp {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        self.source.height(x, z).max(self.min).min(self.max)
+    }
+}
+
+struct Terrace {
+    source: Box<dyn HeightSource>,
+    steps: Vec<f32>,
+}
+
+impl HeightSource for Terrace {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        let value = self.source.height(x, z);
+
+        // NaN counts as below the first step, `max` picks the step over it
+        let first = self.steps[0];
+        let last = self.steps[self.steps.len() - 1];
+        if value.is_nan() || value <= first || value >= last {
+            return value.max(first).min(last);
+        }
+
+        let upper = self.steps.partition_point(|&step| step <= value);
+        let (a, b) = (self.steps[upper - 1], self.steps[upper]);
+        let t = (value - a) / (b - a);
+
+        // Ease in so most of the interval sits close to the lower step
+        a + (b - a) * t * t
+    }
+}
+
+struct Warp {
+    source: Box<dyn HeightSource>,
+    warp_x: Box<dyn HeightSource>,
+    warp_z: Box<dyn HeightSource>,
+    strength: f64,
+}
+
+impl HeightSource for Warp {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        let dx = self.warp_x.height(x, z) as f64 * self.strength;
+        let dz = self.warp_z.height(x, z) as f64 * self.strength;
+
+        self.source.height(x + dx, z + dz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const MALFORMED: [&str; 5] = [
+        r#"{ "type": "curve", "source": { "type": "constant", "value": 1.0 }, "points": [] }"#,
+        r#"{ "type": "terrace", "source": { "type": "constant", "value": 1.0 }, "steps": [0.0] }"#,
+        r#"{ "type": "min", "sources": [] }"#,
+        r#"{ "type": "max", "sources": [] }"#,
+        r#"{ "type": "add", "sources": [{ "type": "constant", "value": 1.0 }, { "type": "max", "sources": [] }] }"#,
+    ];
+
+    #[test]
+    fn malformed_recipes_are_rejected_when_loaded() {
+        let path = std::env::temp_dir().join(format!("procedural_generation_{}_malformed.json", std::process::id()));
+
+        for recipe in MALFORMED {
+            fs::write(&path, recipe).unwrap();
+            let error = HeightNode::from_file(&path).unwrap_err();
+
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", recipe);
+            assert!(serde_json::from_str::<HeightNode>(recipe).unwrap().validate().is_err(), "{}", recipe);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Max needs at least one source")]
+    fn malformed_recipes_do_not_build() {
+        serde_json::from_str::<HeightNode>(MALFORMED[3]).unwrap().build(0);
+    }
+
+    fn constant(value: f32) -> Box<HeightNode> {
+        Box::new(HeightNode::Constant { value })
+    }
+
+    fn height_of(node: HeightNode) -> f32 {
+        node.build(0).height(0.3, -0.2)
+    }
+
+    #[test]
+    fn select_blends_over_the_falloff() {
+        let select = |mask| HeightNode::Select {
+            mask: constant(mask),
+            low: constant(-10.0),
+            high: constant(10.0),
+            threshold: 0.5,
+            falloff: 0.25,
+        };
+
+        assert_eq!(height_of(select(0.0)), -10.0);
+        assert_eq!(height_of(select(0.25)), -10.0);
+        assert_eq!(height_of(select(0.5)), 0.0);
+        assert_eq!(height_of(select(0.75)), 10.0);
+        assert_eq!(height_of(select(1.0)), 10.0);
+
+        // Smoothstep eases out of the low side
+        let eased = height_of(select(0.3));
+        assert!(eased > -10.0 && eased < -10.0 + 20.0 * 0.1, "{}", eased);
+    }
+
+    #[test]
+    fn curve_interpolates_between_its_points() {
+        // Out of order on purpose, build sorts them
+        let curve = |value| HeightNode::Curve {
+            source: constant(value),
+            points: vec![[1.0, 0.0], [-1.0, 4.0], [0.0, 2.0]],
+        };
+
+        assert_eq!(height_of(curve(-5.0)), 4.0);
+        assert_eq!(height_of(curve(-0.5)), 3.0);
+        assert_eq!(height_of(curve(0.0)), 2.0);
+        assert_eq!(height_of(curve(0.75)), 0.5);
+        assert_eq!(height_of(curve(5.0)), 0.0);
+        assert_eq!(height_of(curve(f32::NAN)), 4.0);
+    }
+
+    #[test]
+    fn terrace_eases_towards_the_lower_step() {
+        let terrace = |value| HeightNode::Terrace {
+            source: constant(value),
+            steps: vec![10.0, 0.0, 20.0],
+        };
+
+        assert_eq!(height_of(terrace(-5.0)), 0.0);
+        assert_eq!(height_of(terrace(0.0)), 0.0);
+        assert_eq!(height_of(terrace(5.0)), 2.5);
+        assert_eq!(height_of(terrace(10.0)), 10.0);
+        assert_eq!(height_of(terrace(15.0)), 12.5);
+        assert_eq!(height_of(terrace(25.0)), 20.0);
+        assert_eq!(height_of(terrace(f32::NAN)), 0.0);
+    }
+
+    #[test]
+    fn warp_offsets_the_sample_position() {
+        let noise = || Box::new(HeightNode::Noise {
+            kind: NoiseKind::Perlin,
+            params: NoiseParams::default(),
+        });
+        let warp = HeightNode::Warp {
+            source: noise(),
+            warp_x: constant(0.5),
+            warp_z: constant(-0.25),
+            strength: 0.2,
+        };
+
+        let (warped, source) = (warp.build(3), noise().build(3));
+        for &(x, z) in &[(0.0, 0.0), (0.3, -0.2), (1.7, 2.9)] {
+            assert_eq!(warped.height(x, z), source.height(x + 0.1, z - 0.05));
+        }
+    }
+
+    #[test]
+    fn min_and_max_of_one_source_are_that_source() {
+        for kind in ["min", "max"] {
+            let recipe = format!(r#"{{ "type": "{}", "sources": [{{ "type": "constant", "value": 4.0 }}] }}"#, kind);
+            let node: HeightNode = serde_json::from_str(&recipe).unwrap();
+
+            assert_eq!(node.validate(), Ok(()));
+            assert_eq!(node.build(0).height(0.3, -0.2), 4.0);
+        }
+    }
+}
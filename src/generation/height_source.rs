@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use noise::{
+    Billow, HybridMulti, MultiFractal, NoiseFn, OpenSimplex, Perlin, RidgedMulti, Seedable, Value,
+    Worley,
+};
+
+//
+// A function of the X-Z plane giving the terrain height at that point. Terrain samples it
+// on a grid spanning roughly [-0.5, 0.5] on both axes. Implementations must be
+// deterministic so the same parameters always produce the same heightfield
+//
+pub trait HeightSource: Send + Sync {
+    fn height(&self, x: f64, z: f64) -> f32;
+}
+
+impl<S: HeightSource + ?Sized> HeightSource for Box<S> {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        (**self).height(x, z)
+    }
+}
+
+//
+// Parameters shared by every noise source. The defaults reproduce the original three-octave
+// Perlin terrain
+//
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseParams {
+    pub seed: u32,
+    // Number of layers of noise summed together
+    pub octaves: usize,
+    // Frequency of the first octave
+    pub frequency: f64,
+    // Frequency multiplier between successive octaves
+    pub lacunarity: f64,
+    // Amplitude multiplier between successive octaves
+    pub persistence: f64,
+    // Scale applied to the final value, in world units
+    pub amplitude: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            octaves: 3,
+            frequency: 3.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            amplitude: 25.0,
+        }
+    }
+}
+
+//
+// Sums `octaves` layers of a single noise function, each layer `lacunarity` times the
+// frequency and `persistence` times the amplitude of the previous one
+//
+pub struct Layered<N> {
+    noise: N,
+    params: NoiseParams,
+}
+
+impl<N: NoiseFn<[f64; 2]> + Send + Sync> HeightSource for Layered<N> {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        let mut sum = 0.0;
+        let mut frequency = self.params.frequency;
+        let mut amplitude = 1.0;
+
+        for _ in 0..self.params.octaves {
+            sum += amplitude * self.noise.get([frequency * x, frequency * z]);
+            frequency *= self.params.lacunarity;
+            amplitude *= self.params.persistence;
+        }
+
+        sum as f32 * self.params.amplitude
+    }
+}
+
+pub type PerlinSource = Layered<Perlin>;
+pub type OpenSimplexSource = Layered<OpenSimplex>;
+pub type WorleySource = Layered<Worley>;
+pub type ValueSource = Layered<Value>;
+
+impl Layered<Perlin> {
+    pub fn new(params: NoiseParams) -> Self {
+        Self {
+            noise: Perlin::new().set_seed(params.seed),
+            params,
+        }
+    }
+}
+
+impl Layered<OpenSimplex> {
+    pub fn new(params: NoiseParams) -> Self {
+        Self {
+            noise: OpenSimplex::new().set_seed(params.seed),
+            params,
+        }
+    }
+}
+
+impl Layered<Worley> {
+    // Uses the distance to the nearest feature point, giving cellular terrain
+    pub fn new(params: NoiseParams) -> Self {
+        Self {
+            noise: Worley::new().set_seed(params.seed).enable_range(true),
+            params,
+        }
+    }
+}
+
+impl Layered<Value> {
+    pub fn new(params: NoiseParams) -> Self {
+        Self {
+            noise: Value::new().set_seed(params.seed),
+            params,
+        }
+    }
+}
+
+//
+// One of the multifractal generators from the noise crate, which combine their octaves
+// themselves instead of a plain sum
+//
+pub struct Fractal<F> {
+    fractal: F,
+    amplitude: f32,
+}
+
+impl<F> Fractal<F>
+where
+    F: MultiFractal + Seedable + Default,
+{
+    pub fn new(params: NoiseParams) -> Self {
+        let fractal = F::default()
+            .set_seed(params.seed)
+            .set_octaves(params.octaves)
+            .set_frequency(params.frequency)
+            .set_lacunarity(params.lacunarity)
+            .set_persistence(params.persistence);
+
+        Self {
+            fractal,
+            amplitude: params.amplitude,
+        }
+    }
+}
+
+impl<F: NoiseFn<[f64; 2]> + Send + Sync> HeightSource for Fractal<F> {
+    fn height(&self, x: f64, z: f64) -> f32 {
+        self.fractal.get([x, z]) as f32 * self.amplitude
+    }
+}
+
+pub type RidgedMultiSource = Fractal<RidgedMulti>;
+pub type BillowSource = Fractal<Billow>;
+pub type HybridMultiSource = Fractal<HybridMulti>;
+
+//
+// Names every available noise source so one can be picked at runtime
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseKind {
+    Perlin,
+    OpenSimplex,
+    Worley,
+    Value,
+    RidgedMulti,
+    Billow,
+    HybridMulti,
+}
+
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 7] = [
+        NoiseKind::Perlin,
+        NoiseKind::OpenSimplex,
+        NoiseKind::Worley,
+        NoiseKind::Value,
+        NoiseKind::RidgedMulti,
+        NoiseKind::Billow,
+        NoiseKind::HybridMulti,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseKind::Perlin => "perlin",
+            NoiseKind::OpenSimplex => "open_simplex",
+            NoiseKind::Worley => "worley",
+            NoiseKind::Value => "value",
+            NoiseKind::RidgedMulti => "ridged_multi",
+            NoiseKind::Billow => "billow",
+            NoiseKind::HybridMulti => "hybrid_multi",
+        }
+    }
+
+    pub fn source(&self, params: NoiseParams) -> Box<dyn HeightSource> {
+        match self {
+            NoiseKind::Perlin => Box::new(PerlinSource::new(params)),
+            NoiseKind::OpenSimplex => Box::new(OpenSimplexSource::new(params)),
+            NoiseKind::Worley => Box::new(WorleySource::new(params)),
+            NoiseKind::Value => Box::new(ValueSource::new(params)),
+            NoiseKind::RidgedMulti => Box::new(RidgedMultiSource::new(params)),
+            NoiseKind::Billow => Box::new(BillowSource::new(params)),
+            NoiseKind::HybridMulti => Box::new(HybridMultiSource::new(params)),
+        }
+    }
+}
+
+impl fmt::Display for NoiseKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NoiseKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NoiseKind::ALL
+            .iter()
+            .find(|kind| kind.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown noise kind: {}", s))
+    }
+}
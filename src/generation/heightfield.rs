@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::generation::height_source::HeightSource;
+
+//
+// Maps normalized 0..1 samples, as stored in heightmap files, onto world-space heights
+//
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl HeightRange {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    pub fn denormalize(&self, sample: f32) -> f32 {
+        self.min + sample * (self.max - self.min)
+    }
+
+    pub fn normalize(&self, height: f32) -> f32 {
+        if self.max > self.min {
+            (height - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        }
+    }
+}
+
+//
+// Grid of heights with `width` samples along the X axis and `depth` samples along the Z
+// axis, stored row by row along X so the sample at (x, z) lives at `x * depth + z`
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightfield {
+    width: u32,
+    depth: u32,
+    heights: Vec<f32>,
+}
+
+impl Heightfield {
+    pub fn new(width: u32, depth: u32) -> Self {
+        Self::from_heights(width, depth, vec![0f32; Self::sample_count(width, depth)])
+    }
+
+    pub fn from_heights(width: u32, depth: u32, heights: Vec<f32>) -> Self {
+        assert_eq!(
+            heights.len(),
+            Self::sample_count(width, depth),
+            "Expected {}x{} heights",
+            width,
+            depth
+        );
+
+        Self {
+            width,
+            depth,
+            heights,
+        }
+    }
+
+    // Samples in a `width` by `depth` grid, multiplied as usize so large grids do not wrap
+    // around in u32
+    fn sample_count(width: u32, depth: u32) -> usize {
+        (width as usize)
+            .checked_mul(depth as usize)
+            .unwrap_or_else(|| panic!("Heightfield of {}x{} samples is too large", width, depth))
+    }
+
+    // Samples `source` over the grid. Coordinates are normalized against the longest side
+    // so non-square grids are not stretched
+    pub fn generate(width: u32, depth: u32, source: &dyn HeightSource) -> Self {
+        let mut heightfield = Self::new(width, depth);
+
+        let extent = width.max(depth) as f64;
+
+        for x in 0..width {
+            for z in 0..depth {
+                let nx = (x as f64 / extent) - 0.5;
+                let nz = (z as f64 / extent) - 0.5;
+
+                heightfield.set(x, z, source.height(nx, nz));
+            }
+        }
+
+        heightfield
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn index(&self, x: u32, z: u32) -> usize {
+        debug_assert!(x < self.width && z < self.depth);
+        x as usize * self.depth as usize + z as usize
+    }
+
+    pub fn get(&self, x: u32, z: u32) -> f32 {
+        self.heights[self.index(x, z)]
+    }
+
+    pub fn set(&mut self, x: u32, z: u32, height: f32) {
+        let index = self.index(x, z);
+        self.heights[index] = height;
+    }
+
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    pub fn heights_mut(&mut self) -> &mut [f32] {
+        &mut self.heights
+    }
+
+    // Lowest and highest sample
+    pub fn range(&self) -> HeightRange {
+        let (min, max) = self
+            .heights
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &h| {
+                (min.min(h), max.max(h))
+            });
+
+        HeightRange::new(min, max)
+    }
+}
+
+//
+// A processing step run over the whole heightfield after it has been generated, such as
+// erosion
+//
+pub trait HeightfieldPass {
+    fn apply(&self, heightfield: &mut Heightfield);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::height_source::{NoiseParams, PerlinSource};
+
+    // Pins the output of a seed so changes to the generator that would alter existing maps are
+    // caught
+    #[test]
+    fn seed_reproduces_pinned_heights() {
+        let source = PerlinSource::new(NoiseParams {
+            seed: 1234,
+            ..Default::default()
+        });
+        let heightfield = Heightfield::generate(16, 16, &source);
+
+        let pinned = [
+            (0, 0, -2.4691358),
+            (3, 7, -4.245639),
+            (9, 4, 10.856016),
+            (15, 2, 20.748817),
+            (15, 15, 5.331516),
+        ];
+
+        for &(x, z, height) in &pinned {
+            assert_eq!(heightfield.get(x, z).to_bits(), f32::to_bits(height), "height at ({}, {})", x, z);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use vek::vec::Vec2;
+
+use crate::generation::heightfield::{Heightfield, HeightfieldPass};
+
+//
+// Parameters of the droplet erosion simulation. Distances are in grid cells. The simulation
+// runs on heights rescaled to 0..1 over the heightfield's range, so the defaults behave the
+// same whatever the terrain amplitude
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HydraulicErosionParams {
+    pub seed: u32,
+    // Number of droplets simulated
+    pub droplets: u32,
+    // Maximum number of steps a droplet takes before it is discarded
+    pub max_lifetime: u32,
+    // How much a droplet keeps its previous direction instead of following the slope, 0..1
+    pub inertia: f32,
+    // Multiplier on how much sediment a droplet can carry
+    pub sediment_capacity: f32,
+    // Carrying capacity a droplet keeps even on flat ground
+    pub min_sediment_capacity: f32,
+    // Fraction of the free capacity eroded per step, 0..1
+    pub erosion_rate: f32,
+    // Fraction of the excess sediment deposited per step, 0..1
+    pub deposition_rate: f32,
+    // Fraction of water lost per step, 0..1
+    pub evaporation_rate: f32,
+    pub gravity: f32,
+    pub initial_water: f32,
+    pub initial_speed: f32,
+    // Radius of the area a droplet erodes around itself
+    pub brush_radius: u32,
+}
+
+impl Default for HydraulicErosionParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            droplets: 100_000,
+            max_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation_rate: 0.01,
+            gravity: 4.0,
+            initial_water: 1.0,
+            initial_speed: 1.0,
+            brush_radius: 3,
+        }
+    }
+}
+
+//
+// Particle based hydraulic erosion. Droplets spawn at random positions, roll downhill picking
+// up sediment while they speed up and dropping it when they slow down or evaporate, which
+// carves valleys and fills basins. The same parameters always produce the same result
+//
+pub struct HydraulicErosion {
+    params: HydraulicErosionParams,
+    // Offsets and unnormalized weights of the cells within the brush radius
+    brush: Vec<(i32, i32, f32)>,
+}
+
+impl HydraulicErosion {
+    pub fn new(params: HydraulicErosionParams) -> Self {
+        let radius = params.brush_radius as i32;
+
+        let mut brush = Vec::new();
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                let distance = ((dx * dx + dz * dz) as f32).sqrt();
+                if distance < radius as f32 + 0.5 {
+                    brush.push((dx, dz, (radius as f32 + 0.5 - distance).max(0.0)));
+                }
+            }
+        }
+
+        Self { params, brush }
+    }
+
+    pub fn params(&self) -> &HydraulicErosionParams {
+        &self.params
+    }
+
+    // Erodes `amount` spread over the brush around cell (x, z) and returns how much was removed
+    fn erode(&self, heightfield: &mut Heightfield, x: i32, z: i32, amount: f32) -> f32 {
+        let (width, depth) = (heightfield.width() as i32, heightfield.depth() as i32);
+
+        let in_bounds = |&&(dx, dz, _): &&(i32, i32, f32)| {
+            let (bx, bz) = (x + dx, z + dz);
+            bx >= 0 && bx < width && bz >= 0 && bz < depth
+        };
+
+        let total_weight: f32 = self.brush.iter().filter(in_bounds).map(|b| b.2).sum();
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+
+        for &(dx, dz, weight) in self.brush.iter().filter(in_bounds) {
+            let index = heightfield.index((x + dx) as u32, (z + dz) as u32);
+            heightfield.heights_mut()[index] -= amount * weight / total_weight;
+        }
+
+        amount
+    }
+}
+
+impl HeightfieldPass for HydraulicErosion {
+    fn apply(&self, heightfield: &mut Heightfield) {
+        let params = &self.params;
+        let (width, depth) = (heightfield.width(), heightfield.depth());
+        let (max_x, max_z) = ((width - 1) as f32, (depth - 1) as f32);
+
+        let range = heightfield.range();
+        let (min_height, scale) = (range.min, range.max - range.min);
+        if scale <= 0.0 {
+            return;
+        }
+        for height in heightfield.heights_mut() {
+            *height = (*height - min_height) / scale;
+        }
+
+        let mut rng = XorShift::new(params.seed);
+
+        for _ in 0..params.droplets {
+            let mut pos = Vec2::new(rng.next_f32() * max_x, rng.next_f32() * max_z);
+            let mut dir = Vec2::new(0.0f32, 0.0);
+            let mut speed = params.initial_speed;
+            let mut water = params.initial_water;
+            let mut sediment = 0.0;
+
+            for _ in 0..params.max_lifetime {
+                let (cell_x, cell_z) = (pos.x as u32, pos.y as u32);
+                let offset = Vec2::new(pos.x - cell_x as f32, pos.y - cell_z as f32);
+
+                let (height, gradient) = height_and_gradient(heightfield, pos);
+
+                // Blend the previous direction with the downhill direction
+                dir = dir * params.inertia - gradient * (1.0 - params.inertia);
+                let len = dir.magnitude();
+                if len <= f32::EPSILON {
+                    break;
+                }
+                dir /= len;
+                pos += dir;
+
+                if pos.x < 0.0 || pos.x >= max_x || pos.y < 0.0 || pos.y >= max_z {
+                    break;
+                }
+
+                let delta_height = height_and_gradient(heightfield, pos).0 - height;
+
+                let capacity = (-delta_height * speed * water * params.sediment_capacity)
+                    .max(params.min_sediment_capacity);
+
+                if sediment > capacity || delta_height > 0.0 {
+                    // Fill the pit behind an uphill move, otherwise drop the excess
+                    let amount = if delta_height > 0.0 {
+                        delta_height.min(sediment)
+                    } else {
+                        (sediment - capacity) * params.deposition_rate
+                    };
+                    sediment -= amount;
+
+                    // Deposit bilinearly onto the four corners of the cell the droplet left
+                    let corners = [
+                        (cell_x, cell_z, (1.0 - offset.x) * (1.0 - offset.y)),
+                        (cell_x + 1, cell_z, offset.x * (1.0 - offset.y)),
+                        (cell_x, cell_z + 1, (1.0 - offset.x) * offset.y),
+                        (cell_x + 1, cell_z + 1, offset.x * offset.y),
+                    ];
+                    for &(x, z, weight) in &corners {
+                        let index = heightfield.index(x, z);
+                        heightfield.heights_mut()[index] += amount * weight;
+                    }
+                } else {
+                    // Never dig deeper than the drop, that would carve a pit behind the droplet
+                    let amount = ((capacity - sediment) * params.erosion_rate).min(-delta_height);
+                    sediment += self.erode(heightfield, cell_x as i32, cell_z as i32, amount);
+                }
+
+                speed = (speed * speed - delta_height * params.gravity).max(0.0).sqrt();
+                water *= 1.0 - params.evaporation_rate;
+            }
+        }
+
+        for height in heightfield.heights_mut() {
+            *height = *height * scale + min_height;
+        }
+    }
+}
+
+// Bilinearly interpolated height and gradient at a position inside the grid
+fn height_and_gradient(heightfield: &Heightfield, pos: Vec2<f32>) -> (f32, Vec2<f32>) {
+    let (x, z) = (pos.x as u32, pos.y as u32);
+    let (u, v) = (pos.x - x as f32, pos.y - z as f32);
+
+    let h00 = heightfield.get(x, z);
+    let h10 = heightfield.get(x + 1, z);
+    let h01 = heightfield.get(x, z + 1);
+    let h11 = heightfield.get(x + 1, z + 1);
+
+    let gradient = Vec2::new(
+        (h10 - h00) * (1.0 - v) + (h11 - h01) * v,
+        (h01 - h00) * (1.0 - u) + (h11 - h10) * u,
+    );
+    let height = h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+
+    (height, gradient)
+}
+
+// Small xorshift generator so droplet placement only depends on the seed, not on the
+// platform or a dependency's implementation
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u32) -> Self {
+        // Spread the seed over the state with splitmix64, xorshift needs a non-zero state
+        let mut z = (seed as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        XorShift(z.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
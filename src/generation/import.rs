@@ -0,0 +1,427 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+use png::{BitDepth, ColorType, Decoder, Transformations};
+use serde::{Deserialize, Serialize};
+
+use crate::generation::heightfield::{HeightRange, Heightfield};
+
+//
+// Heightmap importers. Every format stores normalized samples which are mapped onto
+// `range`. Image columns become the terrain's X axis and rows its Z axis
+//
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawFormat {
+    // Unsigned 16-bit integers
+    R16,
+    // 32-bit floats, expected to be normalized to 0..1
+    R32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeightmapFormat {
+    Png,
+    Pgm,
+    Raw {
+        format: RawFormat,
+        endianness: Endianness,
+    },
+}
+
+//
+// Sidecar written next to exported heightmaps as `<file>.json`, recording what is needed to
+// import the file back into the same terrain
+//
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightmapInfo {
+    pub width: u32,
+    pub depth: u32,
+    pub format: HeightmapFormat,
+    pub range: HeightRange,
+}
+
+impl HeightmapInfo {
+    pub fn sidecar_path<P: AsRef<Path>>(heightmap: P) -> PathBuf {
+        let mut path = heightmap.as_ref().as_os_str().to_owned();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+
+    // Reads the sidecar of `heightmap`, if there is one
+    pub fn load<P: AsRef<Path>>(heightmap: P) -> io::Result<Option<Self>> {
+        let file = match File::open(Self::sidecar_path(heightmap)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, heightmap: P) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(Self::sidecar_path(heightmap))?);
+
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    // Imports `heightmap` as described by this sidecar
+    pub fn import<P: AsRef<Path>>(&self, heightmap: P) -> io::Result<Heightfield> {
+        let heightfield = match self.format {
+            HeightmapFormat::Png => import_png(heightmap, self.range)?,
+            HeightmapFormat::Pgm => import_pgm(heightmap, self.range)?,
+            HeightmapFormat::Raw { format, endianness } => {
+                import_raw(heightmap, self.width, self.depth, format, endianness, self.range)?
+            }
+        };
+
+        if (heightfield.width(), heightfield.depth()) != (self.width, self.depth) {
+            return Err(invalid_data(format!(
+                "Heightmap is {}x{} but its sidecar expects {}x{}",
+                heightfield.width(),
+                heightfield.depth(),
+                self.width,
+                self.depth
+            )));
+        }
+
+        Ok(heightfield)
+    }
+}
+
+// Reads an 8 or 16-bit grayscale PNG, the alpha channel of gray-alpha images is ignored.
+// Decoded with the png crate directly as the image crate strips 16-bit samples down to 8 bits
+pub fn import_png<P: AsRef<Path>>(path: P, range: HeightRange) -> io::Result<Heightfield> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+    // Expand lower bit depths to 8 bits but keep 16-bit samples intact
+    decoder.set_transformations(Transformations::EXPAND);
+
+    let (info, mut reader) = decoder.read_info()?;
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data)?;
+
+    // Expansion keeps 16-bit samples as they are but png 0.15 reports them as 8-bit, so the
+    // sample size comes from the file header and the channel count from the decoded rows
+    let header = reader.info();
+    match header.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => {}
+        other => {
+            return Err(invalid_data(format!(
+                "Expected a grayscale heightmap, found {:?}",
+                other
+            )))
+        }
+    }
+    let sample_size = if header.bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+    let channels = info.line_size / (info.width as usize * sample_size);
+
+    // PNG stores 16-bit samples big-endian, the alpha channel is skipped
+    let samples: Vec<f32> = if sample_size == 2 {
+        data.chunks_exact(2)
+            .step_by(channels)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]) as f32 / u16::MAX as f32)
+            .collect()
+    } else {
+        data.iter()
+            .step_by(channels)
+            .map(|&v| v as f32 / u8::MAX as f32)
+            .collect()
+    };
+
+    from_rows(info.width, info.height, &samples, range)
+}
+
+// Reads a headless RAW heightmap of `width` by `depth` samples stored row by row
+pub fn import_raw<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    depth: u32,
+    format: RawFormat,
+    endianness: Endianness,
+    range: HeightRange,
+) -> io::Result<Heightfield> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let sample_size = match format {
+        RawFormat::R16 => 2,
+        RawFormat::R32 => 4,
+    };
+
+    let expected = sample_count(width, depth)?
+        .checked_mul(sample_size)
+        .ok_or_else(|| too_large(width, depth))?;
+    if data.len() != expected {
+        return Err(invalid_data(format!(
+            "Expected {} bytes for a {}x{} {:?} heightmap, found {}",
+            expected,
+            width,
+            depth,
+            format,
+            data.len()
+        )));
+    }
+
+    let samples: Vec<f32> = match (format, endianness) {
+        (RawFormat::R16, Endianness::Little) => data
+            .chunks_exact(2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        (RawFormat::R16, Endianness::Big) => data
+            .chunks_exact(2)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        (RawFormat::R32, Endianness::Little) => data
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+        (RawFormat::R32, Endianness::Big) => data
+            .chunks_exact(4)
+            .map(|v| f32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+    };
+
+    from_rows(width, depth, &samples, range)
+}
+
+// Reads an ASCII (P2) or binary (P5) PGM image
+pub fn import_pgm<P: AsRef<Path>>(path: P, range: HeightRange) -> io::Result<Heightfield> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let mut header = PgmHeader { data: &data, pos: 0 };
+
+    let magic = header.token()?;
+    let width: u32 = header.number()?;
+    let depth: u32 = header.number()?;
+    let max_value: u32 = header.number()?;
+
+    if max_value == 0 || max_value > u16::MAX as u32 {
+        return Err(invalid_data(format!("Invalid PGM maximum value: {}", max_value)));
+    }
+
+    let count = sample_count(width, depth)?;
+    let samples: Vec<u32> = match magic.as_str() {
+        "P2" => (0..count)
+            .map(|_| header.number())
+            .collect::<io::Result<_>>()?,
+        "P5" => {
+            // A single whitespace character separates the header from the samples
+            let body = &data[(header.pos + 1).min(data.len())..];
+            let sample_size = if max_value < 256 { 1 } else { 2 };
+
+            if body.len() / sample_size < count {
+                return Err(invalid_data("PGM file is truncated"));
+            }
+
+            if sample_size == 1 {
+                body[..count].iter().map(|&v| v as u32).collect()
+            } else {
+                body.chunks_exact(2)
+                    .take(count)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]) as u32)
+                    .collect()
+            }
+        }
+        _ => return Err(invalid_data(format!("Not a grayscale PGM file: {}", magic))),
+    };
+
+    let samples: Vec<f32> = samples
+        .iter()
+        .map(|&v| v.min(max_value) as f32 / max_value as f32)
+        .collect();
+
+    from_rows(width, depth, &samples, range)
+}
+
+// Builds a heightfield from normalized samples stored one row of `width` samples per Z
+fn from_rows(width: u32, depth: u32, samples: &[f32], range: HeightRange) -> io::Result<Heightfield> {
+    if width < 2 || depth < 2 {
+        return Err(invalid_data(format!(
+            "Heightmap must be at least 2x2, found {}x{}",
+            width, depth
+        )));
+    }
+
+    let mut heightfield = Heightfield::new(width, depth);
+    for z in 0..depth {
+        for x in 0..width {
+            let sample = samples[(z * width + x) as usize];
+            heightfield.set(x, z, range.denormalize(sample));
+        }
+    }
+
+    Ok(heightfield)
+}
+
+// Number of samples in a `width` by `depth` heightmap, an error if it does not fit in memory
+fn sample_count(width: u32, depth: u32) -> io::Result<usize> {
+    (width as usize).checked_mul(depth as usize).ok_or_else(|| too_large(width, depth))
+}
+
+fn too_large(width: u32, depth: u32) -> io::Error {
+    invalid_data(format!("Heightmap of {}x{} samples is too large", width, depth))
+}
+
+fn invalid_data<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+// Whitespace separated tokens of a PGM header, skipping `#` comments
+struct PgmHeader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PgmHeader<'a> {
+    fn token(&mut self) -> io::Result<String> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while self.data.get(self.pos).is_some_and(|&c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(invalid_data("PGM file is truncated")),
+            }
+        }
+
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|c| !c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+
+        Ok(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self) -> io::Result<u32> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| invalid_data(format!("Invalid number in PGM file: {}", token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::BufWriter;
+    use std::path::PathBuf;
+
+    use png::Encoder;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("procedural_generation_{}_{}", std::process::id(), name))
+    }
+
+    // Writes a 3x2 grayscale PNG of `data` at `depth` bits per sample
+    fn write_png(path: &Path, depth: BitDepth, data: &[u8]) {
+        let mut encoder = Encoder::new(BufWriter::new(File::create(path).unwrap()), 3, 2);
+        encoder.set_color(ColorType::Grayscale);
+        encoder.set_depth(depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+    }
+
+    #[test]
+    fn png_keeps_all_16_bits() {
+        let samples: [u16; 6] = [0, 300, 65535, 1000, 257, 40000];
+        let data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_be_bytes()).collect();
+
+        let path = temp_path("16_bit.png");
+        write_png(&path, BitDepth::Sixteen, &data);
+        let heightfield = import_png(&path, HeightRange::new(0.0, u16::MAX as f32)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Stripped down to 8 bits, 300 would come back as 257 and 1000 as 771
+        assert_eq!((heightfield.width(), heightfield.depth()), (3, 2));
+        for (i, &sample) in samples.iter().enumerate() {
+            let (x, z) = (i as u32 % 3, i as u32 / 3);
+            assert!((heightfield.get(x, z) - sample as f32).abs() < 0.01, "sample {} at ({}, {})", sample, x, z);
+        }
+    }
+
+    #[test]
+    fn png_reads_8_bit_samples() {
+        let path = temp_path("8_bit.png");
+        write_png(&path, BitDepth::Eight, &[0, 51, 255, 102, 153, 204]);
+        let heightfield = import_png(&path, HeightRange::new(0.0, 10.0)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(heightfield.get(0, 0), 0.0);
+        assert_eq!(heightfield.get(1, 0), 2.0);
+        assert_eq!(heightfield.get(2, 0), 10.0);
+        assert_eq!(heightfield.get(1, 1), 6.0);
+    }
+
+    #[test]
+    fn imports_ascii_and_binary_pgm() {
+        let range = HeightRange::new(0.0, 10.0);
+
+        let ascii = temp_path("ascii.pgm");
+        fs::write(&ascii, "P2\n# comment\n3 2\n10\n0 5 10\n10 5 0\n").unwrap();
+        let heightfield = import_pgm(&ascii, range).unwrap();
+        fs::remove_file(&ascii).unwrap();
+
+        let binary = temp_path("binary.pgm");
+        let mut data = b"P5 3 2 10\n".to_vec();
+        data.extend_from_slice(&[0, 5, 10, 10, 5, 0]);
+        fs::write(&binary, data).unwrap();
+        let binary_heightfield = import_pgm(&binary, range).unwrap();
+        fs::remove_file(&binary).unwrap();
+
+        assert_eq!(heightfield, binary_heightfield);
+        assert_eq!((heightfield.width(), heightfield.depth()), (3, 2));
+        // Columns run along X and rows along Z
+        assert_eq!(heightfield.get(2, 0), 10.0);
+        assert_eq!(heightfield.get(1, 1), 5.0);
+        assert_eq!(heightfield.get(2, 1), 0.0);
+    }
+
+    #[test]
+    fn raw_import_checks_the_file_size() {
+        let path = temp_path("short.r16");
+        fs::write(&path, [0u8; 10]).unwrap();
+
+        let result = import_raw(&path, 4, 4, RawFormat::R16, Endianness::Little, HeightRange::new(0.0, 1.0));
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn huge_dimensions_are_invalid_data() {
+        let range = HeightRange::new(0.0, 1.0);
+
+        let raw = temp_path("huge.r32");
+        fs::write(&raw, [0u8; 16]).unwrap();
+        let result = import_raw(&raw, u32::MAX, u32::MAX, RawFormat::R32, Endianness::Little, range);
+        fs::remove_file(&raw).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        for header in ["P2 4294967295 4294967295 255\n0 0", "P5 4294967295 4294967295 65535\n\0\0"] {
+            let pgm = temp_path("huge.pgm");
+            fs::write(&pgm, header).unwrap();
+            let result = import_pgm(&pgm, range);
+            fs::remove_file(&pgm).unwrap();
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", header);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use vek::vec::{Vec2, Vec3};
+
+use crate::generation::chunk::{ChunkCoord, ChunkedTerrain, TerrainChunk};
+use crate::mesh::aabb::Aabb;
+use crate::mesh::triangle_mesh::TriangleMesh;
+
+//
+// Fraction of its range after which a level starts morphing into the next coarser one. The
+// morph is over once the range is reached, where the next level takes over
+//
+pub const LOD_MORPH_START: f32 = 0.7;
+
+//
+// Smallest base_range, in patch widths, for which neighbouring patches never differ by more
+// than one level and always agree along their shared edges, see LodTerrain
+//
+pub const MIN_LOD_RANGE: f32 = 5.0 * std::f32::consts::SQRT_2;
+
+//
+// Shape of the LOD quadtree
+//
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LodParams {
+    // Cells along each side of every patch, whatever its level. Must be even, so every other
+    // vertex of a patch lines up with a vertex of the next coarser level
+    pub patch_size: u32,
+    // Level 0 is the full resolution grid, each level above it has half the resolution of
+    // the one below and patches twice as wide
+    pub levels: u32,
+    // How far level 0 reaches, in level 0 patch widths. Each level reaches twice as far as
+    // the one below, the last one as far as the terrain is drawn
+    pub base_range: f32,
+}
+
+impl Default for LodParams {
+    fn default() -> Self {
+        Self {
+            patch_size: 16,
+            levels: 4,
+            base_range: 8.0,
+        }
+    }
+}
+
+impl LodParams {
+    // Checks the quadtree can be built: patches need an even number of cells, there must be
+    // at least one level and no more than keep the widest patch's cell count within a u32,
+    // and base_range must be finite and at least MIN_LOD_RANGE
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.patch_size >= 2 && self.patch_size.is_multiple_of(2)) {
+            return Err(format!("LOD patches need an even number of cells, got {}", self.patch_size));
+        }
+        if self.levels == 0 || self.levels - 1 > self.patch_size.leading_zeros() {
+            return Err(format!(
+                "LOD needs between 1 and {} levels for patches of {} cells, got {}",
+                self.patch_size.leading_zeros() + 1,
+                self.patch_size,
+                self.levels
+            ));
+        }
+        if !(self.base_range >= MIN_LOD_RANGE && self.base_range.is_finite()) {
+            return Err(format!(
+                "LOD levels must reach at least {} patch widths to avoid cracks, got {}",
+                MIN_LOD_RANGE, self.base_range
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+//
+// A node of the quadtree, covering the patch `x`, `z` of its level. Its four children are the
+// patches 2x to 2x + 1 and 2z to 2z + 1 one level down
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LodNode {
+    pub level: u32,
+    pub x: i32,
+    pub z: i32,
+}
+
+impl LodNode {
+    pub fn new(level: u32, x: i32, z: i32) -> Self {
+        Self { level, x, z }
+    }
+
+    pub fn children(&self) -> [LodNode; 4] {
+        let (level, x, z) = (self.level - 1, self.x * 2, self.z * 2);
+        [
+            LodNode::new(level, x, z),
+            LodNode::new(level, x + 1, z),
+            LodNode::new(level, x, z + 1),
+            LodNode::new(level, x + 1, z + 1),
+        ]
+    }
+
+    // Distance between neighbouring vertices of the node, in full resolution grid cells
+    pub fn step(&self) -> i64 {
+        1 << self.level
+    }
+}
+
+//
+// The mesh of one quadtree node: patch_size cells per side, each `step` full resolution
+// cells wide. Alongside every vertex it keeps the height that vertex has on the next coarser
+// level, which for the vertices that level skips is the middle of the coarse edge or diagonal
+// they lie on. Blending the heights into those makes the patch look exactly like the coarser
+// level, see LodTerrain::morph
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodPatch {
+    node: LodNode,
+    chunk: TerrainChunk,
+    morph_heights: Vec<f32>,
+}
+
+impl LodPatch {
+    pub fn node(&self) -> LodNode {
+        self.node
+    }
+
+    pub fn origin(&self) -> Vec3<f32> {
+        self.chunk.origin()
+    }
+
+    // In patch-local coordinates, like a TerrainChunk
+    pub fn mesh(&self) -> &TriangleMesh {
+        self.chunk.mesh()
+    }
+
+    pub fn morph_heights(&self) -> &[f32] {
+        &self.morph_heights
+    }
+
+    // World-space box around the patch, however far it is morphed
+    pub fn bounds(&self) -> Aabb {
+        let bounds = self.chunk.bounds();
+        let (low, high) = self
+            .morph_heights
+            .iter()
+            .fold((bounds.min.y, bounds.max.y), |(low, high), &height| (low.min(height), high.max(height)));
+
+        Aabb::new(Vec3::new(bounds.min.x, low, bounds.min.z), Vec3::new(bounds.max.x, high, bounds.max.z))
+    }
+}
+
+//
+// Level of detail for a world without an edge, after CDLOD (Strugar, "Continuous Distance-
+// Dependent Level of Detail for Rendering Heightmaps"). A quadtree of patches covers the
+// plane around the camera, refined wherever the camera is within the range of the level below,
+// so patches get coarser with distance.
+//
+// Distances are measured to the terrain flattened onto y = 0, so the detail drops as the
+// camera climbs. Over the last part of its range, from LOD_MORPH_START on, a level morphs
+// each vertex into the next coarser one by distance, which the vertex shader does with
+// morph_range, so nothing pops when a patch is swapped for its parent or children. With
+// base_range at least MIN_LOD_RANGE, neighbouring patches are at most one level apart and the
+// finer one is fully morphed along their shared edge, so the edges meet without cracks.
+//
+// Patches are built on the calling thread when selected and dropped when not. Like
+// ChunkStreamer, the patches kept are whatever `upload` turns them into, e.g. an Object
+//
+pub struct LodTerrain<T> {
+    terrain: ChunkedTerrain,
+    params: LodParams,
+    patches: BTreeMap<LodNode, T>,
+}
+
+impl<T> LodTerrain<T> {
+    // Heights and cell spacing come from `terrain`, its chunk size is not used. Panics if the
+    // parameters are not valid, see LodParams::validate
+    pub fn new(terrain: ChunkedTerrain, params: LodParams) -> Self {
+        if let Err(e) = params.validate() {
+            panic!("{}", e);
+        }
+
+        Self {
+            terrain,
+            params,
+            patches: BTreeMap::new(),
+        }
+    }
+
+    pub fn params(&self) -> &LodParams {
+        &self.params
+    }
+
+    // World-space width of a patch of `level`
+    pub fn patch_extent(&self, level: u32) -> f32 {
+        (self.params.patch_size << level) as f32 * self.terrain.spacing()
+    }
+
+    // How far from the camera `level` is drawn, in world units
+    pub fn range(&self, level: u32) -> f32 {
+        self.params.base_range * self.patch_extent(level)
+    }
+
+    // Distances from the camera over which `level` morphs into the next coarser one
+    pub fn morph_range(&self, level: u32) -> Vec2<f32> {
+        let range = self.range(level);
+        Vec2::new(range * LOD_MORPH_START, range)
+    }
+
+    //
+    // How far a vertex of `level` at world position `position` is morphed into the next
+    // coarser level, from 0 to 1. The vertex shader computes the same from morph_range
+    //
+    pub fn morph(&self, level: u32, position: Vec3<f32>, camera: Vec3<f32>) -> f32 {
+        let point = Vec2::new(position.x, position.z);
+        let distance = lod_distance(camera, point, point);
+        let range = self.morph_range(level);
+
+        ((distance - range.x) / (range.y - range.x)).clamp(0.0, 1.0)
+    }
+
+    // Smallest LOD distance from the camera to the area a node covers
+    fn node_distance(&self, node: LodNode, camera: Vec3<f32>) -> f32 {
+        let extent = self.patch_extent(node.level);
+        let min = Vec2::new(node.x as f32, node.z as f32) * extent;
+
+        lod_distance(camera, min, min + extent)
+    }
+
+    //
+    // The patches to draw for a camera at `camera`, coarsest first. Together they cover the
+    // plane up to the range of the last level without overlapping
+    //
+    pub fn select(&self, camera: Vec3<f32>) -> Vec<LodNode> {
+        let top = self.params.levels - 1;
+        let (extent, range) = (self.patch_extent(top), self.range(top));
+        // Roots along one axis that may be within range
+        let roots = |center: f32| ((center - range) / extent).floor() as i32..=((center + range) / extent).floor() as i32;
+
+        let mut selected = Vec::new();
+        for x in roots(camera.x) {
+            for z in roots(camera.z) {
+                let root = LodNode::new(top, x, z);
+                if self.node_distance(root, camera) < range {
+                    self.select_node(root, camera, &mut selected);
+                }
+            }
+        }
+
+        selected.sort();
+        selected.reverse();
+        selected
+    }
+
+    // Splits a node while the camera is within range of the level below it
+    fn select_node(&self, node: LodNode, camera: Vec3<f32>, selected: &mut Vec<LodNode>) {
+        if node.level == 0 || self.node_distance(node, camera) >= self.range(node.level - 1) {
+            selected.push(node);
+            return;
+        }
+
+        for child in node.children() {
+            self.select_node(child, camera, selected);
+        }
+    }
+
+    pub fn build_patch(&self, node: LodNode) -> LodPatch {
+        let (size, step) = (self.params.patch_size, node.step());
+        let first = (node.x as i64 * size as i64, node.z as i64 * size as i64);
+
+        let chunk = TerrainChunk::build(
+            ChunkCoord::new(node.x, node.z),
+            first,
+            size + 1,
+            size + 1,
+            self.terrain.spacing() * step as f32,
+            |x, z| self.terrain.height(x * step, z * step),
+        );
+
+        // Vertices of the coarser level are the even ones, the others end up halfway along the
+        // coarse triangle edge through them, see GridMeshBuilder for the diagonal's direction
+        let vertices = (size + 1) as usize;
+        let height = |x: usize, z: usize| chunk.mesh().positions[x * vertices + z].y;
+        let mut morph_heights = Vec::with_capacity(vertices * vertices);
+        for x in 0..vertices {
+            for z in 0..vertices {
+                morph_heights.push(match (x % 2, z % 2) {
+                    (0, 0) => height(x, z),
+                    (1, 0) => (height(x - 1, z) + height(x + 1, z)) * 0.5,
+                    (0, _) => (height(x, z - 1) + height(x, z + 1)) * 0.5,
+                    _ => (height(x - 1, z - 1) + height(x + 1, z + 1)) * 0.5,
+                });
+            }
+        }
+
+        LodPatch {
+            node,
+            chunk,
+            morph_heights,
+        }
+    }
+
+    //
+    // Selects the patches for a camera at `camera`, dropping the ones no longer selected and
+    // building and uploading the new ones. If `upload` fails the error is returned and the
+    // patch built again on the next update
+    //
+    pub fn update<E, U>(&mut self, camera: Vec3<f32>, mut upload: U) -> Result<(), E>
+    where
+        U: FnMut(LodPatch) -> Result<T, E>,
+    {
+        let selected = self.select(camera);
+        let wanted: BTreeSet<LodNode> = selected.iter().copied().collect();
+        self.patches.retain(|node, _| wanted.contains(node));
+
+        for node in selected {
+            if !self.patches.contains_key(&node) {
+                let patch = upload(self.build_patch(node))?;
+                self.patches.insert(node, patch);
+            }
+        }
+
+        Ok(())
+    }
+
+    // The patches of the last update, coarsest first
+    pub fn patches(&self) -> impl Iterator<Item = (LodNode, &T)> {
+        self.patches.iter().rev().map(|(&node, patch)| (node, patch))
+    }
+
+    pub fn patch_count(&self) -> usize {
+        self.patches.len()
+    }
+}
+
+// Distance from the camera to the nearest point of the rectangle `min` to `max` on y = 0
+fn lod_distance(camera: Vec3<f32>, min: Vec2<f32>, max: Vec2<f32>) -> f32 {
+    let flat = Vec2::new(camera.x, camera.z);
+    let horizontal = flat.distance(Vec2::new(flat.x.clamp(min.x, max.x), flat.y.clamp(min.y, max.y)));
+
+    Vec2::new(horizontal, camera.y).magnitude()
+}
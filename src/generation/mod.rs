@@ -0,0 +1,24 @@
+#![allow(dead_code, unused_imports)]
+pub mod chunk;
+pub mod export;
+pub mod graph;
+pub mod height_source;
+pub mod heightfield;
+pub mod hydraulic_erosion;
+pub mod import;
+pub mod lod;
+pub mod streaming;
+pub mod terrain;
+pub mod thermal_erosion;
+
+pub use self::chunk::*;
+pub use self::export::*;
+pub use self::graph::*;
+pub use self::height_source::*;
+pub use self::heightfield::*;
+pub use self::hydraulic_erosion::*;
+pub use self::import::*;
+pub use self::lod::*;
+pub use self::streaming::*;
+pub use self::terrain::*;
+pub use self::thermal_erosion::*;
@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use serde::{Deserialize, Serialize};
+use vek::vec::{Vec2, Vec3};
+
+use crate::generation::chunk::{ChunkCoord, ChunkedTerrain, TerrainChunk};
+
+//
+// How far around the camera chunks are kept and how quickly they are brought in. Radii are
+// measured from the camera to chunk centres in chunk widths
+//
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingParams {
+    // Chunks closer than this are loaded
+    pub load_radius: f32,
+    // Chunks further than this are unloaded. Keep it above load_radius so chunks along the
+    // edge are not unloaded and loaded again as the camera moves back and forth
+    pub unload_radius: f32,
+    // Chunks handed to the upload function per update, i.e. per frame
+    pub uploads_per_frame: usize,
+    // Chunks being generated or waiting to be uploaded at once, so a fast moving camera does
+    // not queue up chunks it has long left behind
+    pub max_pending: usize,
+    // Threads generating chunks, with none they are generated during update
+    pub workers: usize,
+}
+
+impl Default for StreamingParams {
+    fn default() -> Self {
+        Self {
+            load_radius: 6.0,
+            unload_radius: 7.5,
+            uploads_per_frame: 2,
+            max_pending: 16,
+            workers: thread::available_parallelism().map_or(2, |n| n.get().saturating_sub(1).clamp(1, 4)),
+        }
+    }
+}
+
+impl StreamingParams {
+    // Checks chunks can be streamed: the load radius must be a finite, non-negative distance
+    // no larger than the finite unload radius, and at least one chunk must be generated and
+    // uploaded at a time
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.load_radius >= 0.0 && self.load_radius.is_finite()) {
+            return Err(format!("Load radius must be zero or more chunks, got {}", self.load_radius));
+        }
+        if !(self.unload_radius >= self.load_radius && self.unload_radius.is_finite()) {
+            return Err(format!(
+                "Chunks must be unloaded no closer than they are loaded, got {} and {}",
+                self.unload_radius, self.load_radius
+            ));
+        }
+        if self.uploads_per_frame == 0 || self.max_pending == 0 {
+            return Err("Chunks must be uploaded and generated at least one at a time".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+//
+// Keeps the chunks of a ChunkedTerrain around the camera resident, for a world without an
+// edge. Each update unloads chunks out of range, uploads up to uploads_per_frame of the
+// generated chunks nearest the camera, then hands the nearest missing chunks to the worker
+// threads. Generating is all a worker does; uploading happens in the `upload` function given
+// to update, on the calling thread, as GL objects must be created on the thread owning the
+// context. Resident chunks are whatever `upload` turns a chunk into, e.g. an Object.
+//
+// Which chunks are resident after each update only depends on the camera positions and on
+// which chunks were generated in time. wait_for_generation makes the latter deterministic
+//
+pub struct ChunkStreamer<T> {
+    terrain: ChunkedTerrain,
+    params: StreamingParams,
+    resident: BTreeMap<ChunkCoord, T>,
+    // Generated and waiting to be uploaded
+    ready: BTreeMap<ChunkCoord, TerrainChunk>,
+    // Handed to a worker and not back yet
+    in_flight: BTreeSet<ChunkCoord>,
+    jobs: Option<Sender<ChunkCoord>>,
+    results: Receiver<TerrainChunk>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T> ChunkStreamer<T> {
+    // Panics if the parameters are not valid, see StreamingParams::validate
+    pub fn new(terrain: ChunkedTerrain, params: StreamingParams) -> Self {
+        if let Err(e) = params.validate() {
+            panic!("{}", e);
+        }
+
+        let (jobs, job_queue) = mpsc::channel::<ChunkCoord>();
+        let (done, results) = mpsc::channel();
+        let job_queue = Arc::new(Mutex::new(job_queue));
+
+        let workers = (0..params.workers)
+            .map(|i| {
+                let (terrain, job_queue, done) = (terrain.clone(), job_queue.clone(), done.clone());
+
+                thread::Builder::new()
+                    .name(format!("chunk worker {}", i))
+                    .spawn(move || loop {
+                        // The lock is only held while waiting, not while generating
+                        let job = job_queue.lock().unwrap().recv();
+                        match job {
+                            Ok(coord) => {
+                                if done.send(terrain.build_chunk(coord)).is_err() {
+                                    break;
+                                }
+                            }
+                            // The streamer was dropped
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("Unable to start a chunk worker thread")
+            })
+            .collect();
+
+        Self {
+            terrain,
+            params,
+            resident: BTreeMap::new(),
+            ready: BTreeMap::new(),
+            in_flight: BTreeSet::new(),
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+
+    pub fn terrain(&self) -> &ChunkedTerrain {
+        &self.terrain
+    }
+
+    pub fn params(&self) -> &StreamingParams {
+        &self.params
+    }
+
+    // Resident chunks in coordinate order
+    pub fn resident(&self) -> impl Iterator<Item = (ChunkCoord, &T)> {
+        self.resident.iter().map(|(&coord, chunk)| (coord, chunk))
+    }
+
+    pub fn resident_count(&self) -> usize {
+        self.resident.len()
+    }
+
+    pub fn is_resident(&self, coord: ChunkCoord) -> bool {
+        self.resident.contains_key(&coord)
+    }
+
+    // Chunks being generated or waiting to be uploaded
+    pub fn pending_count(&self) -> usize {
+        self.in_flight.len() + self.ready.len()
+    }
+
+    //
+    // Every chunk within load_radius of `position`, nearest first. Those are the chunks
+    // resident once the camera stays there long enough
+    //
+    pub fn chunks_in_range(&self, position: Vec3<f32>) -> Vec<ChunkCoord> {
+        let center = self.terrain.chunk_at(position.x, position.z);
+        let reach = self.params.load_radius.ceil() as i32 + 1;
+
+        let mut chunks: Vec<ChunkCoord> = (center.x - reach..=center.x + reach)
+            .flat_map(|x| (center.z - reach..=center.z + reach).map(move |z| ChunkCoord::new(x, z)))
+            .filter(|&coord| self.distance(coord, position) <= self.params.load_radius)
+            .collect();
+        self.sort_nearest_first(&mut chunks, position);
+
+        chunks
+    }
+
+    fn distance(&self, coord: ChunkCoord, position: Vec3<f32>) -> f32 {
+        chunk_distance(&self.terrain, coord, position)
+    }
+
+    // Ties are broken by coordinate so the order never depends on anything else
+    fn sort_nearest_first(&self, chunks: &mut [ChunkCoord], position: Vec3<f32>) {
+        chunks.sort_by(|&a, &b| {
+            self.distance(a, position)
+                .total_cmp(&self.distance(b, position))
+                .then(a.cmp(&b))
+        });
+    }
+
+    //
+    // Streams the chunks for a camera at `position`, see ChunkStreamer. `upload` turns a
+    // generated chunk into a resident one; if it fails the error is returned and the chunk
+    // generated again later
+    //
+    pub fn update<E, U>(&mut self, position: Vec3<f32>, mut upload: U) -> Result<(), E>
+    where
+        U: FnMut(TerrainChunk) -> Result<T, E>,
+    {
+        self.receive();
+
+        // Unload, including chunks generated for a camera that has since moved on
+        let unload_radius = self.params.unload_radius;
+        let terrain = &self.terrain;
+        let keep = |coord: &ChunkCoord| chunk_distance(terrain, *coord, position) <= unload_radius;
+        self.resident.retain(|coord, _| keep(coord));
+        self.ready.retain(|coord, _| keep(coord));
+
+        let mut nearest: Vec<ChunkCoord> = self.ready.keys().copied().collect();
+        self.sort_nearest_first(&mut nearest, position);
+
+        for coord in nearest.into_iter().take(self.params.uploads_per_frame) {
+            let chunk = self.ready.remove(&coord).expect("chunk is ready");
+            let resident = upload(chunk)?;
+            self.resident.insert(coord, resident);
+        }
+
+        self.request(position);
+
+        Ok(())
+    }
+
+    // Hands the nearest missing chunks to the workers, up to max_pending at once
+    fn request(&mut self, position: Vec3<f32>) {
+        for coord in self.chunks_in_range(position) {
+            if self.pending_count() >= self.params.max_pending {
+                break;
+            }
+            if self.resident.contains_key(&coord) || self.ready.contains_key(&coord) || self.in_flight.contains(&coord) {
+                continue;
+            }
+
+            match &self.jobs {
+                Some(jobs) if !self.workers.is_empty() => {
+                    jobs.send(coord).expect("chunk workers stopped");
+                    self.in_flight.insert(coord);
+                }
+                _ => {
+                    self.ready.insert(coord, self.terrain.build_chunk(coord));
+                }
+            }
+        }
+    }
+
+    // Collects the chunks the workers finished so far
+    fn receive(&mut self) {
+        while let Ok(chunk) = self.results.try_recv() {
+            self.finish(chunk);
+        }
+    }
+
+    fn finish(&mut self, chunk: TerrainChunk) {
+        self.in_flight.remove(&chunk.coord());
+        self.ready.insert(chunk.coord(), chunk);
+    }
+
+    // Blocks until every chunk handed to the workers is generated
+    pub fn wait_for_generation(&mut self) {
+        while !self.in_flight.is_empty() {
+            let chunk = self.results.recv().expect("chunk workers stopped");
+            self.finish(chunk);
+        }
+    }
+}
+
+// Distance from `position` to the centre of a chunk, in chunk widths
+fn chunk_distance(terrain: &ChunkedTerrain, coord: ChunkCoord, position: Vec3<f32>) -> f32 {
+    let center = Vec2::new(coord.x as f32 + 0.5, coord.z as f32 + 0.5);
+    center.distance(Vec2::new(position.x, position.z) / terrain.chunk_extent())
+}
+
+impl<T> Drop for ChunkStreamer<T> {
+    fn drop(&mut self) {
+        // Closing the queue stops the workers once they finish their current chunk
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
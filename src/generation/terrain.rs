@@ -0,0 +1,167 @@
+use vek::vec::{Vec3, Vec2};
+
+use crate::generation::height_source::{HeightSource, NoiseParams, PerlinSource};
+use crate::generation::heightfield::{Heightfield, HeightfieldPass};
+use crate::mesh::grid::GridMeshBuilder;
+use crate::mesh::triangle_mesh::{Topology, TriangleMesh};
+
+//
+// Default grid dimensions used when no explicit size is requested, will make a grid with
+// DEFAULT_GRID_SIZE rows and columns spaced one world unit apart
+//
+pub const DEFAULT_GRID_SIZE: u32 = 500;
+pub const DEFAULT_CELL_SPACING: f32 = 1.0;
+
+//
+// Smallest grid the mesh and normal generation can handle, a single cell of two triangles
+//
+pub const MIN_GRID_SIZE: u32 = 2;
+
+// Where the terrain heights come from
+enum Heights {
+    Source(Box<dyn HeightSource>),
+    Heightfield(Heightfield),
+}
+
+pub struct Terrain {
+    width: u32,
+    depth: u32,
+    spacing: f32,
+    heights: Heights,
+    passes: Vec<Box<dyn HeightfieldPass>>,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_GRID_SIZE,
+            DEFAULT_GRID_SIZE,
+            DEFAULT_CELL_SPACING,
+            PerlinSource::new(NoiseParams::default()),
+        )
+    }
+}
+
+impl Terrain {
+    // Creates a terrain with `width` vertices along the X axis and `depth` vertices along
+    // the Z axis, with neighbouring vertices `spacing` world units apart and heights
+    // sampled from `source`
+    pub fn new<S: HeightSource + 'static>(width: u32, depth: u32, spacing: f32, source: S) -> Self {
+        Self::with_heights(width, depth, spacing, Heights::Source(Box::new(source)))
+    }
+
+    // Creates a terrain from an existing heightfield, e.g. an imported heightmap, with one
+    // vertex per sample
+    pub fn from_heightfield(heightfield: Heightfield, spacing: f32) -> Self {
+        let (width, depth) = (heightfield.width(), heightfield.depth());
+        Self::with_heights(width, depth, spacing, Heights::Heightfield(heightfield))
+    }
+
+    fn with_heights(width: u32, depth: u32, spacing: f32, heights: Heights) -> Self {
+        assert!(
+            width >= MIN_GRID_SIZE && depth >= MIN_GRID_SIZE,
+            "Terrain must be at least {0}x{0} vertices, got {1}x{2}",
+            MIN_GRID_SIZE, width, depth
+        );
+        assert!(spacing > 0.0, "Terrain cell spacing must be positive, got {}", spacing);
+
+        Self {
+            width,
+            depth,
+            spacing,
+            heights,
+            passes: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    // The height source sampled by the terrain, if it was not created from a heightfield
+    pub fn source(&self) -> Option<&dyn HeightSource> {
+        match &self.heights {
+            Heights::Source(source) => Some(source.as_ref()),
+            Heights::Heightfield(_) => None,
+        }
+    }
+
+    // Adds a pass run over the generated heightfield before the mesh is built, passes run in
+    // the order they were added
+    pub fn with_pass<P: HeightfieldPass + 'static>(mut self, pass: P) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    // Samples the height source over the grid and runs every pass over the result
+    pub fn heightfield(&self) -> Heightfield {
+        let mut heightfield = match &self.heights {
+            Heights::Source(source) => Heightfield::generate(self.width, self.depth, source.as_ref()),
+            Heights::Heightfield(heightfield) => heightfield.clone(),
+        };
+
+        for pass in &self.passes {
+            pass.apply(&mut heightfield);
+        }
+
+        heightfield
+    }
+
+    //
+    // Index of the mesh vertex at grid position `x`, `z`. Each column of the grid along Z is a
+    // contiguous run of vertices, so an edited rectangle of the heightfield is re-uploaded
+    // one column at a time, see Object::update_vertices
+    //
+    pub fn vertex_index(&self, x: u32, z: u32) -> usize {
+        self.grid(Topology::Triangles).vertex_index(x, z) as usize
+    }
+
+    // World-space extent of the terrain along the X and Z axes
+    pub fn extent(&self) -> Vec2<f32> {
+        Vec2::new(
+            (self.width - 1) as f32 * self.spacing,
+            (self.depth - 1) as f32 * self.spacing,
+        )
+    }
+}
+
+pub const SEA_LEVEL: f32 = -20.0; 
+
+impl Terrain {
+    // Builds the terrain mesh as triangle strips split by primitive restarts, with smooth
+    // normals and the texture repeated once per cell
+    pub fn mesh(&self) -> TriangleMesh {
+        self.mesh_with_topology(Topology::TriangleStrip)
+    }
+
+    // Builds the terrain mesh as a triangle list or restart strips, see GridMeshBuilder
+    pub fn mesh_with_topology(&self, topology: Topology) -> TriangleMesh {
+        let (width, depth) = (self.width, self.depth);
+        let grid_size_mem = width as usize * depth as usize;
+
+        let height = self.heightfield();
+
+        let mut positions = Vec::with_capacity(grid_size_mem);
+        let mut texture_uvs = Vec::with_capacity(grid_size_mem);
+        for x in 0..width {
+            for z in 0..depth {
+                positions.push(Vec3::new(x as f32 * self.spacing, height.get(x, z), z as f32 * self.spacing));
+                texture_uvs.push(Vec2::new(x as _, z as _));
+            }
+        }
+
+        self.grid(topology).build(positions, texture_uvs)
+    }
+
+    fn grid(&self, topology: Topology) -> GridMeshBuilder {
+        GridMeshBuilder::new(self.width, self.depth).with_topology(topology)
+    }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+
+use crate::generation::heightfield::{Heightfield, HeightfieldPass};
+
+// Neighbour offsets and their distance in cells
+const NEIGHBOURS: [(i32, i32, f32); 8] = [
+    (-1, -1, std::f32::consts::SQRT_2),
+    (-1, 0, 1.0),
+    (-1, 1, std::f32::consts::SQRT_2),
+    (0, -1, 1.0),
+    (0, 1, 1.0),
+    (1, -1, std::f32::consts::SQRT_2),
+    (1, 0, 1.0),
+    (1, 1, std::f32::consts::SQRT_2),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalErosionParams {
+    // Steepest stable slope in degrees, anything steeper slides down
+    pub talus_angle: f32,
+    // Maximum number of iterations, the pass stops earlier once every slope is stable
+    pub iterations: u32,
+    // Fraction of the unstable material moved per iteration, 0..1
+    pub transfer_rate: f32,
+    // Distance in world units between neighbouring samples
+    pub cell_spacing: f32,
+    // Height in world units a slope may exceed the talus angle by and still count as stable
+    pub tolerance: f32,
+}
+
+impl Default for ThermalErosionParams {
+    fn default() -> Self {
+        Self {
+            talus_angle: 30.0,
+            iterations: 50,
+            transfer_rate: 0.5,
+            cell_spacing: 1.0,
+            tolerance: 1e-3,
+        }
+    }
+}
+
+impl ThermalErosionParams {
+    // Checks the parameters can be simulated: the talus angle must be strictly between 0 and
+    // 90, the transfer rate above 0 and at most 1 and the cell spacing positive
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.talus_angle > 0.0 && self.talus_angle < 90.0) {
+            return Err(format!("Talus angle must be between 0 and 90 degrees, got {}", self.talus_angle));
+        }
+        if !(self.transfer_rate > 0.0 && self.transfer_rate <= 1.0) {
+            return Err(format!("Transfer rate must be above 0 and at most 1, got {}", self.transfer_rate));
+        }
+        if !(self.cell_spacing > 0.0 && self.cell_spacing.is_finite()) {
+            return Err(format!("Cell spacing must be positive, got {}", self.cell_spacing));
+        }
+
+        Ok(())
+    }
+}
+
+//
+// Thermal weathering. Wherever the slope to a neighbour exceeds the talus angle, part of the
+// excess material slides down to it, softening cliffs into scree slopes. Material only moves
+// between samples so the total volume of the heightfield is conserved
+//
+pub struct ThermalErosion {
+    params: ThermalErosionParams,
+}
+
+impl ThermalErosion {
+    // Panics if the parameters are not valid, see ThermalErosionParams::validate
+    pub fn new(params: ThermalErosionParams) -> Self {
+        if let Err(e) = params.validate() {
+            panic!("{}", e);
+        }
+
+        Self { params }
+    }
+
+    pub fn params(&self) -> &ThermalErosionParams {
+        &self.params
+    }
+
+    // Height difference allowed between two samples `distance` cells apart
+    pub fn talus_height(&self, distance: f32) -> f32 {
+        self.params.talus_angle.to_radians().tan() * distance * self.params.cell_spacing
+    }
+
+    // Runs the simulation and returns whether every slope ended up within the talus angle
+    pub fn erode(&self, heightfield: &mut Heightfield) -> bool {
+        let talus: Vec<f32> = NEIGHBOURS.iter().map(|n| self.talus_height(n.2)).collect();
+        let mut delta = vec![0f32; heightfield.heights().len()];
+
+        for _ in 0..self.params.iterations {
+            if self.transfer(heightfield, &talus, &mut delta) {
+                return true;
+            }
+
+            for (height, delta) in heightfield.heights_mut().iter_mut().zip(&delta) {
+                *height += delta;
+            }
+        }
+
+        self.transfer(heightfield, &talus, &mut delta)
+    }
+
+    // Works out how much material every sample sheds into `delta`, returns true if nothing
+    // needs to move
+    fn transfer(&self, heightfield: &Heightfield, talus: &[f32], delta: &mut [f32]) -> bool {
+        let (width, depth) = (heightfield.width() as i32, heightfield.depth() as i32);
+
+        let mut stable = true;
+        delta.iter_mut().for_each(|d| *d = 0.0);
+
+        for x in 0..width {
+            for z in 0..depth {
+                let height = heightfield.get(x as u32, z as u32);
+
+                // Excess height over the talus slope towards each lower neighbour
+                let mut excess = [0f32; 8];
+                for (i, &(dx, dz, _)) in NEIGHBOURS.iter().enumerate() {
+                    let (nx, nz) = (x + dx, z + dz);
+                    if nx >= 0 && nx < width && nz >= 0 && nz < depth {
+                        let diff = height - heightfield.get(nx as u32, nz as u32);
+                        excess[i] = (diff - talus[i]).max(0.0);
+                    }
+                }
+
+                let total: f32 = excess.iter().sum();
+                let steepest = excess.iter().cloned().fold(0.0, f32::max);
+                if steepest <= self.params.tolerance {
+                    continue;
+                }
+                stable = false;
+
+                // Moving half the steepest excess at most levels that slope exactly, the rest
+                // is shared between the other unstable neighbours
+                let moved = self.params.transfer_rate * steepest / 2.0;
+                delta[heightfield.index(x as u32, z as u32)] -= moved;
+
+                for (i, &(dx, dz, _)) in NEIGHBOURS.iter().enumerate() {
+                    if excess[i] > 0.0 {
+                        delta[heightfield.index((x + dx) as u32, (z + dz) as u32)] +=
+                            moved * excess[i] / total;
+                    }
+                }
+            }
+        }
+
+        stable
+    }
+}
+
+impl HeightfieldPass for ThermalErosion {
+    fn apply(&self, heightfield: &mut Heightfield) {
+        self.erode(heightfield);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single steep peak in the middle of a flat grid
+    fn spike(size: u32, height: f32) -> Heightfield {
+        let mut heightfield = Heightfield::new(size, size);
+        heightfield.set(size / 2, size / 2, height);
+        heightfield
+    }
+
+    #[test]
+    fn thermal_erosion_conserves_mass() {
+        let mut heightfield = spike(9, 20.0);
+        let volume: f32 = heightfield.heights().iter().sum();
+
+        ThermalErosion::new(ThermalErosionParams::default()).apply(&mut heightfield);
+
+        let eroded: f32 = heightfield.heights().iter().sum();
+        assert!((eroded - volume).abs() < 1e-3, "volume changed from {} to {}", volume, eroded);
+    }
+
+    #[test]
+    fn thermal_erosion_leaves_no_slope_steeper_than_the_talus_angle() {
+        let erosion = ThermalErosion::new(ThermalErosionParams {
+            talus_angle: 45.0,
+            iterations: 10_000,
+            ..Default::default()
+        });
+
+        let mut heightfield = spike(9, 20.0);
+        assert!(erosion.erode(&mut heightfield), "thermal erosion did not converge");
+
+        let tolerance = erosion.params().tolerance;
+        for x in 0..9i32 {
+            for z in 0..9i32 {
+                for (dx, dz) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                    let (nx, nz) = (x + dx, z + dz);
+                    if !(0..9).contains(&nx) || !(0..9).contains(&nz) {
+                        continue;
+                    }
+
+                    let distance = ((dx * dx + dz * dz) as f32).sqrt();
+                    let diff = (heightfield.get(x as u32, z as u32) - heightfield.get(nx as u32, nz as u32)).abs();
+
+                    assert!(
+                        diff <= erosion.talus_height(distance) + tolerance,
+                        "slope between ({}, {}) and ({}, {}) is too steep",
+                        x,
+                        z,
+                        nx,
+                        nz
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn talus_angles_outside_0_to_90_degrees_are_invalid() {
+        for talus_angle in [0.0, -10.0, 90.0, 120.0, f32::NAN] {
+            let params = ThermalErosionParams {
+                talus_angle,
+                ..Default::default()
+            };
+
+            assert!(params.validate().is_err(), "{} was accepted", talus_angle);
+        }
+        assert_eq!(ThermalErosionParams::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn transfer_rates_outside_0_to_1_and_flat_cells_are_invalid() {
+        for transfer_rate in [0.0, -0.5, 1.5, f32::NAN] {
+            let params = ThermalErosionParams {
+                transfer_rate,
+                ..Default::default()
+            };
+
+            assert!(params.validate().is_err(), "transfer rate {} was accepted", transfer_rate);
+        }
+
+        for cell_spacing in [0.0, -1.0, f32::INFINITY, f32::NAN] {
+            let params = ThermalErosionParams {
+                cell_spacing,
+                ..Default::default()
+            };
+
+            assert!(params.validate().is_err(), "cell spacing {} was accepted", cell_spacing);
+        }
+
+        let params = ThermalErosionParams {
+            transfer_rate: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(params.validate(), Ok(()));
+    }
+}
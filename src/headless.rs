@@ -0,0 +1,25 @@
+use std::time::Instant;
+
+use crate::options::Options;
+
+//
+// Batch generation for machines without a display. Builds the terrain and writes the requested
+// exports without creating a window or GL context
+//
+pub fn run(options: Options) {
+    if !options.has_exports() {
+        Options::exit_with_usage("Nothing to generate, give at least one --export-* flag");
+    }
+
+    let start = Instant::now();
+
+    let terrain = options.terrain();
+    options.export(&terrain);
+
+    println!(
+        "Generated {}x{} terrain in {:.2}s",
+        terrain.width(),
+        terrain.depth(),
+        start.elapsed().as_secs_f32()
+    );
+}
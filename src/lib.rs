@@ -0,0 +1,12 @@
+//
+// Procedural terrain generation. `generation` builds heightfields and terrain meshes on the
+// CPU, `mesh` holds and exports those meshes, and `backend` wraps the OpenGL objects used to
+// draw them. Only `backend` and `drawables` need a GL context
+//
+// Lets the derive macros name this crate the same way inside it as outside
+extern crate self as procedural_generation;
+
+pub mod backend;
+pub mod drawables;
+pub mod generation;
+pub mod mesh;
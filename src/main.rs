@@ -1,14 +1,15 @@
-mod backend;
-mod drawables;
+mod headless;
+mod options;
 
-use backend::camera::Camera;
-use backend::object::Object;
-use backend::renderer::{Renderer, Capabilities, ClearFlags, PolygonMode};
-use backend::shader::Shader;
-use backend::texture::Texture;
+use procedural_generation::backend::camera::Camera;
+use procedural_generation::backend::object::Object;
+use procedural_generation::backend::renderer::{Renderer, Capabilities, ClearFlags, PolygonMode};
+use procedural_generation::backend::shader::{Shader, SHADER_POLL_INTERVAL};
+use procedural_generation::backend::texture::Texture;
 
-use backend::CameraMovement;
-use drawables::Terrain;
+use procedural_generation::backend::{CameraMovement, CullStats, Error, FrameUniforms, Frustum, UniformBuffer};
+use procedural_generation::generation::{ChunkStreamer, LodTerrain};
+use options::Options;
 
 use glutin::{
     config::ConfigTemplateBuilder,
@@ -23,7 +24,7 @@ use winit::event::DeviceEvent;
 use winit::{
     dpi::LogicalSize,
     event::{ElementState, Event, KeyEvent, WindowEvent},
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop},
     keyboard::{Key, NamedKey},
     window::WindowBuilder,
 };
@@ -32,6 +33,16 @@ use vek::mat::*;
 use vek::vec::*;
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("generate") {
+        args.remove(0);
+        headless::run(Options::from_args(args));
+        return;
+    }
+
+    let options = Options::from_args(args);
+
     let event_loop = EventLoop::new().unwrap();
 
     let window_builder = WindowBuilder::new()
@@ -61,7 +72,7 @@ fn main() {
         .build(Some(window.raw_window_handle()));
 
     let fallback_context_attributes = ContextAttributesBuilder::new()
-        .with_context_api(ContextApi::Gles(None))
+        .with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
         .build(Some(window.raw_window_handle()));
 
     let legacy_context_attributes = ContextAttributesBuilder::new()
@@ -90,11 +101,40 @@ fn main() {
     let renderer = Renderer::new(gl_context, surface, &display);
     renderer.enable(Capabilities::DEPTH_TEST);
 
-    let terrain_shader = Shader::from_file("shaders/terrain.vert", "shaders/terrain.frag");
+    // Created before the shaders so they bind their Frame block to it when linked
+    let frame_uniforms = UniformBuffer::<FrameUniforms>::new().unwrap_or_else(|e| exit_with_error(e));
+
+    // LOD patches morph between levels in the vertex shader
+    let defines: &[(&str, &str)] = if options.lod { &[("LOD", "1")] } else { &[] };
+    let mut terrain_shader = Shader::from_file_with_defines("shaders/terrain.vert", "shaders/terrain.frag", defines)
+        .unwrap_or_else(|e| exit_with_error(e));
 
-    let (grass, grass_id) = Texture::new("textures/low_def_grass.jpg");
+    let (grass, _) = Texture::new(&options.texture).unwrap_or_else(|e| exit_with_error(e));
+
+    let mut world = if options.lod {
+        World::Lod(LodTerrain::new(options.chunked_terrain(), options.lod_params))
+    } else if options.infinite {
+        World::Streaming(ChunkStreamer::new(options.chunked_terrain(), options.streaming))
+    } else {
+        let terrain = options.terrain();
+        options.export(&terrain);
+
+        let chunks = terrain
+            .chunks(options.chunk_size)
+            .into_iter()
+            .map(|chunk| {
+                let origin = chunk.origin();
+                Object::new(chunk, origin, None).unwrap_or_else(|e| exit_with_error(e))
+            })
+            .collect();
+
+        World::Chunks(chunks)
+    };
 
-    let point_grid = Object::new(Terrain, Vec3::new(0.0, 0.0, 0.0), Some(grass));
+    // Toggled with the wireframe view, colours LOD patches by level
+    let mut lod_debug = false;
+    // Shown in the title, which only changes when they do
+    let mut shown_stats = None;
 
     let mut camera = Camera::new(
         Vec3::new(2.5, 8.0, 2.5),
@@ -104,12 +144,13 @@ fn main() {
         true,
     );
 
-    let mut last_frame = std::time::Instant::now();
-    let mut delta_frame = 0f32;
+    let sky_color = Vec4::new(0.2, 0.3, 0.6, 0.5);
+    let start = std::time::Instant::now();
+    let mut last_frame = start;
 
     event_loop.run(move |event, event_loop| {
         let now = std::time::Instant::now();
-        delta_frame = now.duration_since(last_frame).as_secs_f32();
+        let delta_frame = now.duration_since(last_frame).as_secs_f32();
         last_frame = now;
 
         match event {
@@ -144,24 +185,46 @@ fn main() {
                         }
                         Key::Character("q") => {
                             renderer.polygon_mode(PolygonMode::FILL);
+                            lod_debug = false;
                         }
                         Key::Character("e") => {
                             renderer.polygon_mode(PolygonMode::LINE);
+                            lod_debug = true;
                         }
                         _ => {}
                 }
 
                 _ => {}
             },
-            Event::DeviceEvent { event, .. } => {
-                if let DeviceEvent::MouseMotion { delta } = event {
-                    camera.process_mouse_inputs(delta.0 as _, delta.1 as _);
-                }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                camera.process_mouse_inputs(delta.0 as _, delta.1 as _);
             }
             _ => {}
         }
 
-        renderer.clear(Vec4::new(0.2, 0.3, 0.6, 0.5), ClearFlags::COLOR_DEPTH);
+        // A failed reload keeps drawing with the last program that compiled
+        match terrain_shader.reload_if_changed() {
+            Some(Ok(())) => println!("Reloaded the terrain shader"),
+            Some(Err(e)) => eprintln!("{}", e),
+            None => {}
+        }
+
+        let uploaded = match &mut world {
+            World::Chunks(_) => Ok(()),
+            World::Streaming(streamer) => streamer.update(*camera.get_position(), |chunk| {
+                let origin = chunk.origin();
+                Object::new(chunk, origin, None)
+            }),
+            World::Lod(lod) => lod.update(*camera.get_position(), |patch| {
+                let origin = patch.origin();
+                Object::new(patch, origin, None)
+            }),
+        };
+        if let Err(e) = uploaded {
+            exit_with_error(e);
+        }
+
+        renderer.clear(sky_color, ClearFlags::COLOR_DEPTH);
 
         let projection = Mat4::perspective_rh_zo(
             f32::to_radians(camera.get_zoom()),
@@ -170,17 +233,86 @@ fn main() {
             1000.0,
         );
 
+        let view = camera.get_view_matrix();
+        let frustum = Frustum::from_matrix(&(projection * view));
+        let mut stats = CullStats::default();
+
+        let frame = FrameUniforms {
+            view,
+            projection,
+            camera_position: *camera.get_position(),
+            time: now.duration_since(start).as_secs_f32(),
+            fog_density: 0.002,
+            fog_color: Vec3::from(sky_color),
+            ..Default::default()
+        };
+        frame_uniforms.update(&frame);
+
         terrain_shader.use_program();
-        terrain_shader.set_mat4fv("view", &camera.get_view_matrix());
-        terrain_shader.set_mat4fv("projection", &projection);
-        terrain_shader.set_sampler2D("texture", grass_id);
-        terrain_shader.set_vec3f("light_color", &Vec3::new(1.0, 1.0, 1.0));
-        terrain_shader.set_vec3f("light_pos", &Vec3::new(250.0, 100.0, 250.0));
+        // Only sets anything on OpenGL 2.1, which has no uniform buffers
+        terrain_shader.set_uniform_block(&frame);
+        // The chunks share the one texture, bound to the default unit 0
+        grass.bind();
+        terrain_shader.set_sampler2D("t_texture", 0);
+
+        match &world {
+            World::Chunks(chunks) => {
+                for chunk in chunks.iter().filter(|chunk| stats.count(chunk.is_visible(&frustum))) {
+                    terrain_shader.set_mat4fv("model", &chunk.get_transform());
+                    chunk.draw();
+                }
+            }
+            World::Streaming(streamer) => {
+                for (_, chunk) in streamer.resident().filter(|(_, chunk)| stats.count(chunk.is_visible(&frustum))) {
+                    terrain_shader.set_mat4fv("model", &chunk.get_transform());
+                    chunk.draw();
+                }
+            }
+            World::Lod(lod) => {
+                terrain_shader.set_uniform("lod_debug", &lod_debug);
+
+                for (node, patch) in lod.patches().filter(|(_, patch)| stats.count(patch.is_visible(&frustum))) {
+                    terrain_shader.set_mat4fv("model", &patch.get_transform());
+                    terrain_shader.set_vec2f("lod_morph", &lod.morph_range(node.level));
+                    terrain_shader.set_1i("lod_level", node.level as i32);
+                    patch.draw();
+                }
+            }
+        }
 
-        let model = point_grid.get_transform();
-        terrain_shader.set_mat4fv("model", &model);
-        point_grid.draw();
+        if shown_stats != Some(stats) {
+            window.set_title(&format!("Procedural Generation - {} drawn, {} culled", stats.drawn, stats.culled));
+            shown_stats = Some(stats);
+        }
+
+        if let Err(e) = renderer.swap_buffers() {
+            eprintln!("{}", e);
+            event_loop.exit();
+        }
 
-        renderer.swap_buffers();
+        // Keep drawing while streamed chunks are still on their way, otherwise wake up without
+        // input only so shader edits show up while the window is idle. LOD patches are built
+        // during the update and never wait
+        let streaming = matches!(&world, World::Streaming(streamer) if streamer.pending_count() > 0);
+        if streaming {
+            event_loop.set_control_flow(ControlFlow::Poll);
+        } else {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(now + SHADER_POLL_INTERVAL));
+        }
     }).unwrap();
 }
+
+//
+// What the terrain is drawn from: one object per chunk of a fixed grid, chunks streamed in
+// around the camera, or LOD patches getting coarser away from it
+//
+enum World {
+    Chunks(Vec<Object>),
+    Streaming(ChunkStreamer<Object>),
+    Lod(LodTerrain<Object>),
+}
+
+fn exit_with_error(error: Error) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
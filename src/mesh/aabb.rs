@@ -0,0 +1,103 @@
+use vek::mat::Mat4;
+use vek::vec::Vec3;
+
+//
+// Axis-aligned bounding box, `min` to `max` inclusive on every axis
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        assert!(
+            min.x <= max.x && min.y <= max.y && min.z <= max.z,
+            "Bounding box minimum {} is above its maximum {}",
+            min,
+            max
+        );
+
+        Self { min, max }
+    }
+
+    // Smallest box holding every point, None if there are none
+    pub fn from_points<I: IntoIterator<Item = Vec3<f32>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self { min: first, max: first }, |aabb, point| aabb.including(point)))
+    }
+
+    // The box grown to hold `point`
+    pub fn including(self, point: Vec3<f32>) -> Self {
+        Self {
+            min: Vec3::partial_min(self.min, point),
+            max: Vec3::partial_max(self.max, point),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Self {
+        self.including(other.min).including(other.max)
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    // Half the size of the box along each axis
+    pub fn half_extents(&self) -> Vec3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        std::array::from_fn(|i| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        })
+    }
+
+    pub fn contains(&self, point: Vec3<f32>) -> bool {
+        point.x >= self.min.x
+            && point.y >= self.min.y
+            && point.z >= self.min.z
+            && point.x <= self.max.x
+            && point.y <= self.max.y
+            && point.z <= self.max.z
+    }
+
+    pub fn translated(&self, offset: Vec3<f32>) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    //
+    // Box holding this one after `transform`, e.g. an object's model matrix. Rotations make
+    // it larger than the transformed contents, as they no longer line up with the axes
+    //
+    pub fn transformed(&self, transform: &Mat4<f32>) -> Self {
+        // Arvo, "Transforming Axis-Aligned Bounding Boxes": the new centre plus the absolute
+        // matrix applied to the half extents
+        let center = transform.mul_point(self.center());
+        let half = self.half_extents();
+        let rows = transform.into_row_arrays();
+
+        let extent = Vec3::new(
+            rows[0][0].abs() * half.x + rows[0][1].abs() * half.y + rows[0][2].abs() * half.z,
+            rows[1][0].abs() * half.x + rows[1][1].abs() * half.y + rows[1][2].abs() * half.z,
+            rows[2][0].abs() * half.x + rows[2][1].abs() * half.y + rows[2][2].abs() * half.z,
+        );
+
+        Self {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+}
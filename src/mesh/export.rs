@@ -0,0 +1,361 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde_json::json;
+use vek::vec::Vec3;
+
+use crate::mesh::triangle_mesh::TriangleMesh;
+
+// Name of the single material exported meshes are drawn with
+const MATERIAL_NAME: &str = "grass";
+
+//
+// Writes a Wavefront OBJ and a material library next to it with the same name. The texture is
+// copied alongside them so the files can be moved around together
+//
+pub fn export_obj<P: AsRef<Path>, T: AsRef<Path>>(mesh: &TriangleMesh, path: P, texture: T) -> io::Result<()> {
+    let path = path.as_ref();
+    let mesh = mesh.to_triangle_list();
+
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let texture_name = file_name(texture.as_ref())?;
+    let material_path = path.with_extension("mtl");
+
+    if fs::canonicalize(texture.as_ref())? != fs::canonicalize(directory)?.join(texture_name) {
+        fs::copy(texture.as_ref(), directory.join(texture_name))?;
+    }
+
+    let mut writer = BufWriter::new(File::create(&material_path)?);
+    writeln!(writer, "newmtl {}", MATERIAL_NAME)?;
+    writeln!(writer, "Ka 1.0 1.0 1.0")?;
+    writeln!(writer, "Kd 1.0 1.0 1.0")?;
+    writeln!(writer, "Ks 0.0 0.0 0.0")?;
+    writeln!(writer, "illum 1")?;
+    writeln!(writer, "map_Kd {}", texture_name)?;
+    writer.flush()?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "mtllib {}", file_name(&material_path)?)?;
+    writeln!(writer, "o terrain")?;
+
+    for v in &mesh.positions {
+        writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+    }
+    for vt in &mesh.texture_uvs {
+        writeln!(writer, "vt {} {}", vt.x, vt.y)?;
+    }
+    for vn in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", vn.x, vn.y, vn.z)?;
+    }
+
+    writeln!(writer, "usemtl {}", MATERIAL_NAME)?;
+
+    // OBJ indices start at one
+    let (has_uvs, has_normals) = (!mesh.texture_uvs.is_empty(), !mesh.normals.is_empty());
+    for triangle in mesh.indices.chunks_exact(3) {
+        write!(writer, "f")?;
+        for &index in triangle {
+            let index = index + 1;
+            match (has_uvs, has_normals) {
+                (true, true) => write!(writer, " {0}/{0}/{0}", index)?,
+                (true, false) => write!(writer, " {0}/{0}", index)?,
+                (false, true) => write!(writer, " {0}//{0}", index)?,
+                (false, false) => write!(writer, " {}", index)?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+//
+// Writes a binary glTF 2.0 file. Everything, including the texture, is packed into the single
+// binary buffer of the file
+//
+pub fn export_glb<P: AsRef<Path>, T: AsRef<Path>>(mesh: &TriangleMesh, path: P, texture: T) -> io::Result<()> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const REPEAT: u32 = 10497;
+    const LINEAR: u32 = 9729;
+    const LINEAR_MIPMAP_LINEAR: u32 = 9987;
+    const TRIANGLES: u32 = 4;
+
+    let mesh = mesh.to_triangle_list();
+    let image = fs::read(texture.as_ref())?;
+    let mime_type = match extension(texture.as_ref()).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "glTF textures must be PNG or JPEG images",
+            ))
+        }
+    };
+
+    let mut binary = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+
+    let mut push_view = |binary: &mut Vec<u8>, data: &[u8], target: Option<u32>| {
+        // Every view starts 4-byte aligned
+        binary.resize((binary.len() + 3) & !3, 0);
+
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": binary.len(),
+            "byteLength": data.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        binary.extend_from_slice(data);
+        buffer_views.push(view);
+        buffer_views.len() - 1
+    };
+
+    let (min, max) = mesh.positions.iter().fold(
+        (Vec3::broadcast(f32::INFINITY), Vec3::broadcast(f32::NEG_INFINITY)),
+        |(min, max), &p| (Vec3::partial_min(min, p), Vec3::partial_max(max, p)),
+    );
+
+    let positions: Vec<u8> = mesh.positions.iter().flat_map(|v| v.into_array()).flat_map(f32::to_le_bytes).collect();
+    let view = push_view(&mut binary, &positions, Some(ARRAY_BUFFER));
+    accessors.push(json!({
+        "bufferView": view,
+        "componentType": FLOAT,
+        "count": mesh.vertex_count(),
+        "type": "VEC3",
+        "min": min.into_array(),
+        "max": max.into_array(),
+    }));
+
+    let mut primitive_attributes = json!({ "POSITION": accessors.len() - 1 });
+
+    if !mesh.normals.is_empty() {
+        let normals: Vec<u8> = mesh
+            .normals
+            .iter()
+            .map(|n| n.normalized())
+            .flat_map(|v| v.into_array())
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let view = push_view(&mut binary, &normals, Some(ARRAY_BUFFER));
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": mesh.vertex_count(),
+            "type": "VEC3",
+        }));
+        primitive_attributes["NORMAL"] = json!(accessors.len() - 1);
+    }
+
+    if !mesh.texture_uvs.is_empty() {
+        // glTF puts the texture origin in the top left corner, OpenGL in the bottom left
+        let texture_uvs: Vec<u8> = mesh
+            .texture_uvs
+            .iter()
+            .flat_map(|uv| [uv.x, 1.0 - uv.y])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let view = push_view(&mut binary, &texture_uvs, Some(ARRAY_BUFFER));
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": mesh.vertex_count(),
+            "type": "VEC2",
+        }));
+        primitive_attributes["TEXCOORD_0"] = json!(accessors.len() - 1);
+    }
+
+    let indices: Vec<u8> = mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+    let view = push_view(&mut binary, &indices, Some(ELEMENT_ARRAY_BUFFER));
+    accessors.push(json!({
+        "bufferView": view,
+        "componentType": UNSIGNED_INT,
+        "count": mesh.indices.len(),
+        "type": "SCALAR",
+    }));
+    let indices_accessor = accessors.len() - 1;
+
+    let image_view = push_view(&mut binary, &image, None);
+    binary.resize((binary.len() + 3) & !3, 0);
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": "terrain", "mesh": 0 }],
+        "meshes": [{
+            "name": "terrain",
+            "primitives": [{
+                "attributes": primitive_attributes,
+                "indices": indices_accessor,
+                "material": 0,
+                "mode": TRIANGLES,
+            }],
+        }],
+        "materials": [{
+            "name": MATERIAL_NAME,
+            "pbrMetallicRoughness": {
+                "baseColorTexture": { "index": 0 },
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        }],
+        "textures": [{ "sampler": 0, "source": 0 }],
+        "samplers": [{
+            "magFilter": LINEAR,
+            "minFilter": LINEAR_MIPMAP_LINEAR,
+            "wrapS": REPEAT,
+            "wrapT": REPEAT,
+        }],
+        "images": [{ "bufferView": image_view, "mimeType": mime_type }],
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": binary.len() }],
+    });
+
+    // The JSON chunk is padded with spaces to keep the binary chunk aligned
+    let mut json = serde_json::to_vec(&document)?;
+    json.resize((json.len() + 3) & !3, b' ');
+
+    let length = 12 + 8 + json.len() + 8 + binary.len();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+
+    writer.write_all(&(binary.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&binary)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn file_name(path: &Path) -> io::Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Not a file: {}", path.display())))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::{NoiseParams, PerlinSource, Terrain};
+    use std::path::PathBuf;
+
+    const TEXTURE: &str = "textures/low_def_grass.jpg";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("procedural_generation_{}_{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mesh(width: u32, depth: u32) -> TriangleMesh {
+        Terrain::new(width, depth, 1.5, PerlinSource::new(NoiseParams::default())).mesh()
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize
+    }
+
+    #[test]
+    fn obj_export_parses_back() {
+        let dir = temp_dir("obj");
+        let path = dir.join("terrain.obj");
+        let (width, depth) = (6, 5);
+
+        export_obj(&mesh(width, depth), &path, TEXTURE).unwrap();
+
+        let obj = fs::read_to_string(&path).unwrap();
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+        let vertices = (width * depth) as usize;
+
+        assert_eq!(count("v "), vertices);
+        assert_eq!(count("vn "), vertices);
+        assert_eq!(count("vt "), vertices);
+        assert_eq!(count("f "), (2 * (width - 1) * (depth - 1)) as usize);
+
+        for face in obj.lines().filter(|line| line.starts_with("f ")) {
+            let corners: Vec<&str> = face.split_whitespace().skip(1).collect();
+            assert_eq!(corners.len(), 3);
+
+            for corner in corners {
+                for index in corner.split('/') {
+                    let index: usize = index.parse().unwrap();
+                    assert!((1..=vertices).contains(&index), "index {} out of range", index);
+                }
+            }
+        }
+
+        let mtl = fs::read_to_string(dir.join("terrain.mtl")).unwrap();
+        assert!(obj.contains("mtllib terrain.mtl"));
+        assert!(mtl.contains("map_Kd low_def_grass.jpg"));
+        assert!(dir.join("low_def_grass.jpg").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn glb_export_parses_back() {
+        let dir = temp_dir("glb");
+        let path = dir.join("terrain.glb");
+        let (width, depth) = (6, 5);
+
+        export_glb(&mesh(width, depth), &path, TEXTURE).unwrap();
+        let glb = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(&glb, 4), 2);
+        assert_eq!(u32_at(&glb, 8), glb.len());
+
+        let json_length = u32_at(&glb, 12);
+        assert_eq!(&glb[16..20], b"JSON");
+        let document: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+        let bin_offset = 20 + json_length;
+        assert_eq!(u32_at(&glb, bin_offset), glb.len() - bin_offset - 8);
+        assert_eq!(&glb[bin_offset + 4..bin_offset + 8], b"BIN\0");
+        assert_eq!(document["buffers"][0]["byteLength"], glb.len() - bin_offset - 8);
+
+        let primitive = &document["meshes"][0]["primitives"][0];
+        let accessor = |name: &str| &document["accessors"][primitive["attributes"][name].as_u64().unwrap() as usize];
+
+        let vertices = (width * depth) as u64;
+        assert_eq!(accessor("POSITION")["count"], vertices);
+        assert_eq!(accessor("NORMAL")["count"], vertices);
+        assert_eq!(accessor("TEXCOORD_0")["count"], vertices);
+
+        let indices = &document["accessors"][primitive["indices"].as_u64().unwrap() as usize];
+        assert_eq!(indices["count"], 3 * 2 * (width - 1) as u64 * (depth - 1) as u64);
+
+        let image = &document["images"][0];
+        assert_eq!(image["mimeType"], "image/jpeg");
+        let view = &document["bufferViews"][image["bufferView"].as_u64().unwrap() as usize];
+        assert_eq!(view["byteLength"], fs::metadata(TEXTURE).unwrap().len());
+    }
+}
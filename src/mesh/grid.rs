@@ -0,0 +1,121 @@
+use vek::vec::{Vec2, Vec3};
+
+use crate::mesh::triangle_mesh::{Topology, TriangleMesh, PRIMITIVE_RESTART_INDEX};
+
+//
+// Builds the triangles of a regular grid of `width` vertices along X by `depth` along Z. The
+// vertices are laid out one column along Z after the other, so vertex (x, z) is number
+// x * depth + z. Triangles wind counter clockwise seen from above, i.e. from +Y
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridMeshBuilder {
+    width: u32,
+    depth: u32,
+    topology: Topology,
+}
+
+impl GridMeshBuilder {
+    // A grid needs at least one cell, so two vertices along each axis
+    pub fn new(width: u32, depth: u32) -> Self {
+        assert!(width >= 2 && depth >= 2, "A grid mesh needs at least 2x2 vertices, got {}x{}", width, depth);
+
+        Self {
+            width,
+            depth,
+            topology: Topology::TriangleStrip,
+        }
+    }
+
+    //
+    // Triangles gives an indexed triangle list of two triangles per cell. TriangleStrip, the
+    // default, gives one strip per column of cells with PRIMITIVE_RESTART_INDEX between them,
+    // about a third of the indices
+    //
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn vertex_index(&self, x: u32, z: u32) -> u32 {
+        x * self.depth + z
+    }
+
+    pub fn indices(&self) -> Vec<u32> {
+        match self.topology {
+            Topology::Triangles => self.triangle_list(),
+            Topology::TriangleStrip => self.restart_strips(),
+        }
+    }
+
+    fn triangle_list(&self) -> Vec<u32> {
+        let cells = ((self.width - 1) * (self.depth - 1)) as usize;
+        let mut indices = Vec::with_capacity(cells * 6);
+
+        for x in 0..self.width - 1 {
+            for z in 0..self.depth - 1 {
+                let near_left = self.vertex_index(x, z);
+                let near_right = self.vertex_index(x + 1, z);
+                let far_left = self.vertex_index(x, z + 1);
+                let far_right = self.vertex_index(x + 1, z + 1);
+
+                indices.extend_from_slice(&[near_right, near_left, far_right]);
+                indices.extend_from_slice(&[far_right, near_left, far_left]);
+            }
+        }
+
+        indices
+    }
+
+    // Each column of cells zig-zags from its right edge to its left edge up the Z axis
+    fn restart_strips(&self) -> Vec<u32> {
+        let columns = (self.width - 1) as usize;
+        let mut indices = Vec::with_capacity(columns * (self.depth as usize * 2 + 1));
+
+        for x in 0..self.width - 1 {
+            if x > 0 {
+                indices.push(PRIMITIVE_RESTART_INDEX);
+            }
+
+            for z in 0..self.depth {
+                indices.push(self.vertex_index(x + 1, z));
+                indices.push(self.vertex_index(x, z));
+            }
+        }
+
+        indices
+    }
+
+    // A mesh over vertices laid out as the grid expects, with smooth normals
+    pub fn build(&self, positions: Vec<Vec3<f32>>, texture_uvs: Vec<Vec2<f32>>) -> TriangleMesh {
+        assert_eq!(
+            positions.len(),
+            (self.width * self.depth) as usize,
+            "A {}x{} grid needs one position per vertex",
+            self.width,
+            self.depth
+        );
+
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: Vec::new(),
+            texture_uvs,
+            indices: self.indices(),
+            topology: self.topology,
+        };
+        mesh.compute_normals();
+
+        mesh
+    }
+}
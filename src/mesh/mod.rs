@@ -0,0 +1,10 @@
+#![allow(dead_code, unused_imports)]
+pub mod aabb;
+pub mod export;
+pub mod grid;
+pub mod triangle_mesh;
+
+pub use self::aabb::*;
+pub use self::export::*;
+pub use self::grid::*;
+pub use self::triangle_mesh::*;
@@ -0,0 +1,138 @@
+use vek::vec::{Vec2, Vec3};
+
+use crate::mesh::aabb::Aabb;
+
+//
+// Index that ends a triangle strip and starts a new one, the largest u32 as GL's fixed index
+// primitive restart expects. The renderer enables primitive restart for it
+//
+pub const PRIMITIVE_RESTART_INDEX: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+    Triangles,
+    // Every index after the first two adds a triangle. Separate strips are either joined by
+    // repeated indices or split by PRIMITIVE_RESTART_INDEX
+    TriangleStrip,
+}
+
+//
+// Indexed mesh kept on the CPU, independent of any GPU buffers so it can be generated and
+// exported without a GL context. Normals and texture coordinates are either empty or hold
+// one entry per position
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+    pub texture_uvs: Vec<Vec2<f32>>,
+    pub indices: Vec<u32>,
+    pub topology: Topology,
+}
+
+impl TriangleMesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Box around every vertex, None for a mesh without any
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions.iter().copied())
+    }
+
+    // Number of triangles drawn, not counting the degenerate ones joining strips
+    pub fn triangle_count(&self) -> usize {
+        self.triangles().count()
+    }
+
+    // Every non-degenerate triangle, with strip triangles wound the same way as the first one
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        let triangles: Box<dyn Iterator<Item = [u32; 3]>> = match self.topology {
+            Topology::Triangles => Box::new(self.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])),
+            // Every other triangle of a strip is wound the other way round, counting from the
+            // start of the strip
+            Topology::TriangleStrip => Box::new(
+                self.indices
+                    .split(|&index| index == PRIMITIVE_RESTART_INDEX)
+                    .flat_map(|strip| {
+                        strip
+                            .windows(3)
+                            .enumerate()
+                            .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                    }),
+            ),
+        };
+
+        triangles.filter(|&[a, b, c]| a != b && b != c && a != c)
+    }
+
+    //
+    // Smooth normals from the triangles around each vertex, summing the face normals
+    // unnormalized so bigger triangles weigh more. Triangles are taken to face the way they
+    // are wound counter clockwise, whatever the topology, and vertices no triangle uses get
+    // a zero normal
+    //
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::zero(); self.vertex_count()];
+
+        for [a, b, c] in self.triangles() {
+            let [a, b, c] = [a, b, c].map(|i| i as usize);
+            let face_normal = (self.positions[b] - self.positions[a]).cross(self.positions[c] - self.positions[a]);
+
+            normals[a] += face_normal;
+            normals[b] += face_normal;
+            normals[c] += face_normal;
+        }
+
+        for normal in &mut normals {
+            if normal.magnitude_squared() > 0.0 {
+                normal.normalize();
+            }
+        }
+
+        self.normals = normals;
+    }
+
+    //
+    // The same mesh as a plain triangle list, the layout OBJ and glTF expect. Both formats
+    // treat counter clockwise triangles as front facing while the renderer draws both sides,
+    // so the list is flipped if its triangles face away from the vertex normals. That is
+    // decided over the whole mesh so a few odd normals cannot flip single triangles
+    //
+    pub fn to_triangle_list(&self) -> TriangleMesh {
+        let mut indices: Vec<u32> = self.triangles().flatten().collect();
+
+        if self.faces_against_normals(&indices) {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        TriangleMesh {
+            positions: self.positions.clone(),
+            normals: self.normals.clone(),
+            texture_uvs: self.texture_uvs.clone(),
+            indices,
+            topology: Topology::Triangles,
+        }
+    }
+
+    fn faces_against_normals(&self, triangle_list: &[u32]) -> bool {
+        if self.normals.is_empty() {
+            return false;
+        }
+
+        let agreement: f32 = triangle_list
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+                let face_normal = (self.positions[b] - self.positions[a]).cross(self.positions[c] - self.positions[a]);
+                let vertex_normal = self.normals[a] + self.normals[b] + self.normals[c];
+
+                face_normal.dot(vertex_normal).signum()
+            })
+            .sum();
+
+        agreement < 0.0
+    }
+}
@@ -0,0 +1,399 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use procedural_generation::generation::{
+    export_normal_map, export_png, export_raw, export_slope_map, import_pgm, import_png,
+    import_raw, ChunkedTerrain, Endianness, HeightNode, LodParams, HeightRange, HeightSource, Heightfield, HeightmapInfo,
+    HydraulicErosion, HydraulicErosionParams, NoiseKind, NoiseParams, RawFormat, StreamingParams,
+    Terrain, ThermalErosion, ThermalErosionParams, DEFAULT_CELL_SPACING, DEFAULT_CHUNK_SIZE, DEFAULT_GRID_SIZE,
+    MIN_GRID_SIZE,
+};
+use procedural_generation::mesh::{export_glb, export_obj};
+
+pub const USAGE: &str = "Usage: procedural_generation [generate] [--config <file.json>]
+                            [--size <width> <depth>] [--spacing <f>] [--chunk-size <cells>]
+                            [--infinite] [--view-distance <chunks>] [--lod] [--lod-levels <n>]
+                            [--seed <u32>] [--noise <kind>] [--octaves <n>]
+                            [--frequency <f>] [--lacunarity <f>] [--persistence <f>] [--amplitude <f>]
+                            [--recipe <file.json>] [--erosion <droplets>]
+                            [--thermal <iterations>] [--talus <degrees>]
+                            [--heightmap <file>] [--height-range <min> <max>]
+                            [--raw-size <width> <depth>] [--big-endian]
+                            [--texture <file>] [--export-heightmap <file>] [--export-mesh <file>]
+                            [--export-normal-map <file.png>] [--export-slope-map <file.png>]
+
+`generate` writes the requested exports without opening a window.
+Options can also be read from a JSON config file, flags override its values.
+Noise kinds: perlin, open_simplex, worley, value, ridged_multi, billow, hybrid_multi
+--infinite streams chunks around the camera from the noise instead of drawing a fixed grid,
+--view-distance sets how many chunks away they are loaded (6 by default). --lod draws the
+same world as patches that get coarser away from the camera, over --lod-levels levels (4 by
+default). Heightmaps and erosion only apply to the fixed grid.
+A recipe replaces the single noise source with a noise graph, see recipes/continents.json
+Heightmaps can be 8/16-bit grayscale .png, .pgm, or headerless .raw/.r16 (16-bit) and .r32
+(float) files, whose samples are mapped onto the height range (-25 to 25 by default).
+Heightmaps are exported as 16-bit .png, .raw/.r16 or .r32 with a .json sidecar holding the
+height range, which is picked up again when the heightmap is loaded.
+Meshes are exported as Wavefront .obj (with a .mtl material) or binary glTF .glb files";
+
+pub const GRASS_TEXTURE: &str = "textures/low_def_grass.jpg";
+
+//
+// Options parsed from the command line or a JSON config file, using the same names as the
+// flags with underscores, e.g. `{ "noise": "billow", "noise_params": { "seed": 7 } }`
+//
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    pub width: u32,
+    pub depth: u32,
+    pub spacing: f32,
+    // Cells along each side of the chunks the terrain is drawn in
+    pub chunk_size: u32,
+    pub infinite: bool,
+    pub streaming: StreamingParams,
+    pub lod: bool,
+    pub lod_params: LodParams,
+    pub noise: NoiseKind,
+    pub noise_params: NoiseParams,
+    pub recipe: Option<String>,
+    pub erosion_droplets: u32,
+    pub thermal: ThermalErosionParams,
+    pub heightmap: Option<String>,
+    pub height_range: HeightRange,
+    pub raw_size: Option<(u32, u32)>,
+    pub raw_endianness: Endianness,
+    pub texture: String,
+    pub export_heightmap: Option<String>,
+    pub export_mesh: Option<String>,
+    pub export_normal_map: Option<String>,
+    pub export_slope_map: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            width: DEFAULT_GRID_SIZE,
+            depth: DEFAULT_GRID_SIZE,
+            spacing: DEFAULT_CELL_SPACING,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            infinite: false,
+            streaming: StreamingParams::default(),
+            lod: false,
+            lod_params: LodParams::default(),
+            noise: NoiseKind::Perlin,
+            noise_params: NoiseParams {
+                seed: Self::time_seed(),
+                ..Default::default()
+            },
+            recipe: None,
+            erosion_droplets: 0,
+            thermal: ThermalErosionParams {
+                iterations: 0,
+                ..Default::default()
+            },
+            heightmap: None,
+            height_range: HeightRange::new(-25.0, 25.0),
+            raw_size: None,
+            raw_endianness: Endianness::Little,
+            texture: GRASS_TEXTURE.to_string(),
+            export_heightmap: None,
+            export_mesh: None,
+            export_normal_map: None,
+            export_slope_map: None,
+        }
+    }
+}
+
+impl Options {
+    // Parses the flags in `args`, on top of the config file given with --config if any
+    pub fn from_args(args: Vec<String>) -> Self {
+        let mut options = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Self::from_file(&Self::value::<String>("--config", args.get(i + 1).cloned())),
+            None => Options::default(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let params = &mut options.noise_params;
+
+            match arg.as_str() {
+                "--config" => {
+                    args.next();
+                }
+                "--size" => {
+                    options.width = Self::value(&arg, args.next());
+                    options.depth = Self::value(&arg, args.next());
+                }
+                "--spacing" => options.spacing = Self::value(&arg, args.next()),
+                "--chunk-size" => options.chunk_size = Self::value(&arg, args.next()),
+                "--infinite" => options.infinite = true,
+                "--view-distance" => {
+                    let streaming = &mut options.streaming;
+                    streaming.load_radius = Self::value(&arg, args.next());
+                    streaming.unload_radius = streaming.load_radius + 1.5;
+                }
+                "--lod" => options.lod = true,
+                "--lod-levels" => options.lod_params.levels = Self::value(&arg, args.next()),
+                "--seed" => params.seed = Self::value(&arg, args.next()),
+                "--noise" => options.noise = Self::value(&arg, args.next()),
+                "--octaves" => params.octaves = Self::value(&arg, args.next()),
+                "--frequency" => params.frequency = Self::value(&arg, args.next()),
+                "--lacunarity" => params.lacunarity = Self::value(&arg, args.next()),
+                "--persistence" => params.persistence = Self::value(&arg, args.next()),
+                "--amplitude" => params.amplitude = Self::value(&arg, args.next()),
+                "--recipe" => options.recipe = Some(Self::value(&arg, args.next())),
+                "--erosion" => options.erosion_droplets = Self::value(&arg, args.next()),
+                "--thermal" => options.thermal.iterations = Self::value(&arg, args.next()),
+                "--talus" => options.thermal.talus_angle = Self::value(&arg, args.next()),
+                "--heightmap" => options.heightmap = Some(Self::value(&arg, args.next())),
+                "--height-range" => {
+                    options.height_range.min = Self::value(&arg, args.next());
+                    options.height_range.max = Self::value(&arg, args.next());
+                }
+                "--raw-size" => {
+                    options.raw_size = Some((Self::value(&arg, args.next()), Self::value(&arg, args.next())));
+                }
+                "--big-endian" => options.raw_endianness = Endianness::Big,
+                "--texture" => options.texture = Self::value(&arg, args.next()),
+                "--export-heightmap" => options.export_heightmap = Some(Self::value(&arg, args.next())),
+                "--export-mesh" => options.export_mesh = Some(Self::value(&arg, args.next())),
+                "--export-normal-map" => options.export_normal_map = Some(Self::value(&arg, args.next())),
+                "--export-slope-map" => options.export_slope_map = Some(Self::value(&arg, args.next())),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => Self::exit_with_usage(&format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        // Checked once flags and the config file are merged, so either can set the values
+        if let Err(e) = options.validate() {
+            Self::exit_with_usage(&e);
+        }
+
+        options
+    }
+
+    // Checks the values the terrain would otherwise panic on
+    fn validate(&self) -> Result<(), String> {
+        if self.width < MIN_GRID_SIZE || self.depth < MIN_GRID_SIZE {
+            return Err(format!(
+                "--size must be at least {0} {0}, got {1} {2}",
+                MIN_GRID_SIZE, self.width, self.depth
+            ));
+        }
+        // Mesh indices are 32-bit
+        if self.width as u64 * self.depth as u64 > u32::MAX as u64 {
+            return Err(format!("--size {} {} has too many vertices", self.width, self.depth));
+        }
+        if !(self.spacing > 0.0 && self.spacing.is_finite()) {
+            return Err(format!("--spacing must be positive, got {}", self.spacing));
+        }
+        if self.chunk_size == 0 {
+            return Err("--chunk-size must be at least 1".to_string());
+        }
+
+        self.thermal.validate()?;
+        self.streaming.validate()?;
+        self.lod_params.validate()
+    }
+
+    fn from_file(path: &str) -> Self {
+        File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| Self::exit_with_usage(&format!("Unable to load config {}: {}", path, e)))
+    }
+
+    // Whether any file is to be written
+    pub fn has_exports(&self) -> bool {
+        self.export_heightmap.is_some()
+            || self.export_mesh.is_some()
+            || self.export_normal_map.is_some()
+            || self.export_slope_map.is_some()
+    }
+
+    // Parses the value following `flag`, exiting with the usage string if it is missing or invalid
+    fn value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+        value
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| Self::exit_with_usage(&format!("Missing or invalid value for {}", flag)))
+    }
+
+    // Builds the terrain described by the options, an imported heightmap takes precedence over
+    // generated heights. The passes are run once up front so the exports and the mesh all
+    // share the result
+    pub fn terrain(&self) -> Terrain {
+        let mut terrain = match &self.heightmap {
+            Some(path) => Terrain::from_heightfield(self.import_heightmap(path), self.spacing),
+            None => Terrain::new(self.width, self.depth, self.spacing, self.height_source()),
+        };
+
+        if self.erosion_droplets > 0 {
+            terrain = terrain.with_pass(HydraulicErosion::new(HydraulicErosionParams {
+                seed: self.noise_params.seed,
+                droplets: self.erosion_droplets,
+                ..Default::default()
+            }));
+        }
+
+        if self.thermal.iterations > 0 {
+            terrain = terrain.with_pass(ThermalErosion::new(ThermalErosionParams {
+                cell_spacing: self.spacing,
+                ..self.thermal
+            }));
+        }
+
+        Terrain::from_heightfield(terrain.heightfield(), self.spacing)
+    }
+
+    //
+    // Terrain without an edge sampling the same heights, with features the size they have on
+    // the fixed grid
+    //
+    pub fn chunked_terrain(&self) -> ChunkedTerrain {
+        ChunkedTerrain::new(self.height_source(), self.chunk_size, self.spacing)
+            .with_noise_scale(self.width.max(self.depth) as f64)
+    }
+
+    // Writes every export requested for `terrain`
+    pub fn export(&self, terrain: &Terrain) {
+        if let Some(path) = &self.export_heightmap {
+            self.export_heightmap(terrain, path);
+        }
+        if let Some(path) = &self.export_mesh {
+            self.export_mesh(terrain, path);
+        }
+        if let Some(path) = &self.export_normal_map {
+            export_normal_map(&terrain.heightfield(), terrain.spacing(), path)
+                .unwrap_or_else(|e| panic!("Unable to export normal map {}: {}", path, e));
+            println!("Exported normal map to {}", path);
+        }
+        if let Some(path) = &self.export_slope_map {
+            export_slope_map(&terrain.heightfield(), terrain.spacing(), path)
+                .unwrap_or_else(|e| panic!("Unable to export slope map {}: {}", path, e));
+            println!("Exported slope map to {}", path);
+        }
+    }
+
+    // Loads the heightmap at `path`, picking the format from its extension
+    fn import_heightmap(&self, path: &str) -> Heightfield {
+        println!("Loading heightmap {}", path);
+
+        // Heightmaps exported by this program describe themselves in a sidecar
+        match HeightmapInfo::load(path) {
+            Ok(Some(info)) => {
+                return info
+                    .import(path)
+                    .unwrap_or_else(|e| panic!("Unable to load heightmap {}: {}", path, e))
+            }
+            Ok(None) => {}
+            Err(e) => panic!("Unable to read the sidecar of {}: {}", path, e),
+        }
+
+        let raw = |format| {
+            let (width, depth) = self.raw_size.unwrap_or_else(|| {
+                Self::exit_with_usage("RAW heightmaps need --raw-size <width> <depth>")
+            });
+            import_raw(path, width, depth, format, self.raw_endianness, self.height_range)
+        };
+
+        let heightfield = match Self::extension(path).as_deref() {
+            Some("png") => import_png(path, self.height_range),
+            Some("pgm") => import_pgm(path, self.height_range),
+            Some("raw") | Some("r16") => raw(RawFormat::R16),
+            Some("r32") => raw(RawFormat::R32),
+            _ => Self::exit_with_usage(&format!("Unknown heightmap format: {}", path)),
+        };
+
+        heightfield.unwrap_or_else(|e| panic!("Unable to load heightmap {}: {}", path, e))
+    }
+
+    // Writes the terrain's heightfield to `path`, picking the format from its extension
+    fn export_heightmap(&self, terrain: &Terrain, path: &str) {
+        let heightfield = terrain.heightfield();
+
+        let info = match Self::extension(path).as_deref() {
+            Some("png") => export_png(&heightfield, path),
+            Some("raw") | Some("r16") => export_raw(&heightfield, path, RawFormat::R16, self.raw_endianness),
+            Some("r32") => export_raw(&heightfield, path, RawFormat::R32, self.raw_endianness),
+            _ => Self::exit_with_usage(&format!("Unknown heightmap format: {}", path)),
+        }
+        .unwrap_or_else(|e| panic!("Unable to export heightmap {}: {}", path, e));
+
+        println!(
+            "Exported {}x{} heightmap to {} with heights {} to {}",
+            info.width, info.depth, path, info.range.min, info.range.max
+        );
+    }
+
+    // Writes the terrain mesh to `path` textured with the terrain texture, picking the format
+    // from its extension
+    fn export_mesh(&self, terrain: &Terrain, path: &str) {
+        let mesh = terrain.mesh();
+
+        match Self::extension(path).as_deref() {
+            Some("obj") => export_obj(&mesh, path, &self.texture),
+            Some("glb") => export_glb(&mesh, path, &self.texture),
+            _ => Self::exit_with_usage(&format!("Unknown mesh format: {}", path)),
+        }
+        .unwrap_or_else(|e| panic!("Unable to export mesh {}: {}", path, e));
+
+        println!(
+            "Exported mesh with {} vertices and {} triangles to {}",
+            mesh.vertex_count(),
+            mesh.triangle_count(),
+            path
+        );
+    }
+
+    fn extension(path: &str) -> Option<String> {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase())
+    }
+
+    // Builds the height source described by the options, a recipe file takes precedence over
+    // the single noise flags
+    fn height_source(&self) -> Box<dyn HeightSource> {
+        match &self.recipe {
+            Some(path) => {
+                println!("Generating terrain from {} with seed {}", path, self.noise_params.seed);
+
+                HeightNode::from_file(path)
+                    .unwrap_or_else(|e| panic!("Unable to load recipe {}: {}", path, e))
+                    .build(self.noise_params.seed)
+            }
+            None => {
+                println!(
+                    "Generating {} terrain with seed {}",
+                    self.noise, self.noise_params.seed
+                );
+
+                self.noise.source(self.noise_params)
+            }
+        }
+    }
+
+    // Seed used when none is given, so every run shows a different map
+    fn time_seed() -> u32 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        (now.as_secs() as u32) ^ now.subsec_nanos()
+    }
+
+    pub fn exit_with_usage(message: &str) -> ! {
+        eprintln!("{}\n{}", message, USAGE);
+        std::process::exit(2);
+    }
+}
@@ -0,0 +1,172 @@
+use procedural_generation::backend::*;
+use procedural_generation::generation::*;
+use procedural_generation::mesh::*;
+use vek::mat::Mat4;
+use vek::vec::{Vec3, Vec4};
+
+// A camera at `eye` looking at `target`, with the projection the viewer uses but a nearer far plane
+fn view_projection(eye: Vec3<f32>, target: Vec3<f32>) -> Mat4<f32> {
+    let projection = Mat4::perspective_rh_zo(60f32.to_radians(), 4.0 / 3.0, 0.1, 100.0);
+    projection * Mat4::look_at_rh(eye, target, Vec3::unit_y())
+}
+
+fn cube(center: Vec3<f32>, half: f32) -> Aabb {
+    Aabb::new(center - half, center + half)
+}
+
+// Whether `point` ends up in the clip volume GL clips against, with `margin` to spare
+fn in_clip_volume(view_projection: &Mat4<f32>, point: Vec3<f32>, margin: f32) -> bool {
+    let clip = *view_projection * Vec4::from_point(point);
+    let w = clip.w - margin;
+    clip.x.abs() <= w && clip.y.abs() <= w && clip.z.abs() <= w
+}
+
+#[test]
+fn boxes_are_classified_against_each_plane() {
+    let frustum = Frustum::from_matrix(&view_projection(Vec3::zero(), -Vec3::unit_z()));
+
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, 0.0, -10.0), 1.0)), Containment::Inside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(3.0, -2.0, -50.0), 5.0)), Containment::Inside);
+
+    // Behind the camera, past the far plane and off to each side
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, 0.0, 10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, 0.0, -110.0), 5.0)), Containment::Outside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(-30.0, 0.0, -10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(30.0, 0.0, -10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, -30.0, -10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, 30.0, -10.0), 1.0)), Containment::Outside);
+
+    // Around the camera, straddling the far plane and a side plane
+    assert_eq!(frustum.classify(&cube(Vec3::zero(), 1.0)), Containment::Intersecting);
+    assert_eq!(frustum.classify(&cube(Vec3::new(0.0, 0.0, -100.0), 5.0)), Containment::Intersecting);
+    assert_eq!(frustum.classify(&cube(Vec3::new(-7.7, 0.0, -10.0), 1.0)), Containment::Intersecting);
+
+    // Large enough to hold the whole frustum
+    assert_eq!(frustum.classify(&cube(Vec3::zero(), 500.0)), Containment::Intersecting);
+    assert!(frustum.intersects(&cube(Vec3::zero(), 500.0)));
+}
+
+#[test]
+fn planes_match_the_clip_volume() {
+    let view_projection = view_projection(Vec3::new(3.0, 5.0, -2.0), Vec3::new(40.0, -3.0, -60.0));
+    let frustum = Frustum::from_matrix(&view_projection);
+
+    for plane in frustum.planes() {
+        assert!((plane.normal.magnitude() - 1.0).abs() < 1e-5, "{:?} is not normalized", plane);
+    }
+
+    // Random boxes from a fixed xorshift sequence
+    let mut state = 0x9e37_79b9_u32;
+    let mut next = |low: f32, high: f32| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        low + (high - low) * (state as f32 / u32::MAX as f32)
+    };
+
+    let mut outcomes = [0; 3];
+    for _ in 0..2000 {
+        let center = Vec3::new(next(-150.0, 150.0), next(-150.0, 150.0), next(-150.0, 150.0));
+        let half = Vec3::new(next(0.1, 20.0), next(0.1, 20.0), next(0.1, 20.0));
+        let aabb = Aabb::new(center - half, center + half);
+
+        // Points inside and on the surface of the box
+        let samples: Vec<Vec3<f32>> = (0..5 * 5 * 5)
+            .map(|i| Vec3::new((i % 5) as f32, (i / 5 % 5) as f32, (i / 25) as f32) / 4.0)
+            .map(|t| aabb.min + (aabb.max - aabb.min) * t)
+            .collect();
+
+        for &point in &samples {
+            if in_clip_volume(&view_projection, point, 1e-3) {
+                assert!(frustum.contains(point), "{} is in view but outside the frustum", point);
+            }
+            if !in_clip_volume(&view_projection, point, -1e-3) {
+                assert!(!frustum.contains(point), "{} is out of view but inside the frustum", point);
+            }
+        }
+
+        let containment = frustum.classify(&aabb);
+        match containment {
+            Containment::Outside => assert!(
+                samples.iter().all(|&point| !in_clip_volume(&view_projection, point, 1e-3)),
+                "{:?} is partly in view but classified outside",
+                aabb
+            ),
+            Containment::Inside => assert!(
+                aabb.corners().iter().all(|&corner| in_clip_volume(&view_projection, corner, -1e-3)),
+                "{:?} is partly out of view but classified inside",
+                aabb
+            ),
+            Containment::Intersecting => {}
+        }
+        outcomes[containment as usize] += 1;
+    }
+
+    // The boxes were spread enough to cover every case
+    assert!(outcomes.iter().all(|&count| count > 0), "{:?}", outcomes);
+}
+
+#[test]
+fn transformed_boxes_hold_the_transformed_corners() {
+    let aabb = Aabb::new(Vec3::new(-1.0, 0.5, 2.0), Vec3::new(3.0, 4.0, 2.5));
+
+    let mut model = Mat4::identity();
+    model.scale_3d(Vec3::new(2.0, 0.5, 1.0));
+    model.rotate_3d(35f32.to_radians(), Vec3::new(1.0, 1.0, 0.0).normalized());
+    model.translate_3d(Vec3::new(10.0, -3.0, 7.0));
+
+    let transformed = aabb.transformed(&model);
+    let corners = aabb.corners().map(|corner| model.mul_point(corner));
+    for corner in corners {
+        let grown = Aabb::new(transformed.min - 1e-4, transformed.max + 1e-4);
+        assert!(grown.contains(corner), "{} is outside {:?}", corner, transformed);
+    }
+
+    // The box is as tight as boxes around the corners get
+    let tight = Aabb::from_points(corners).unwrap();
+    assert!((tight.min - transformed.min).magnitude() < 1e-4 && (tight.max - transformed.max).magnitude() < 1e-4);
+
+    // Translations only move the box
+    let translated = aabb.transformed(&Mat4::translation_3d(Vec3::new(1.0, 2.0, 3.0)));
+    assert_eq!(translated, aabb.translated(Vec3::new(1.0, 2.0, 3.0)));
+}
+
+#[test]
+fn chunks_and_patches_are_inside_their_bounds() {
+    let terrain = ChunkedTerrain::new(PerlinSource::new(NoiseParams::default()), 8, 1.5);
+    let chunk = terrain.build_chunk(ChunkCoord::new(-2, 3));
+    let bounds = chunk.bounds();
+
+    for &position in &chunk.mesh().positions {
+        assert!(bounds.contains(chunk.origin() + position));
+    }
+    assert_eq!(bounds, chunk.mesh().bounds().unwrap().translated(chunk.origin()));
+
+    let lod = LodTerrain::<()>::new(terrain, LodParams { patch_size: 8, levels: 3, base_range: MIN_LOD_RANGE });
+    let patch = lod.build_patch(LodNode::new(2, 1, -1));
+    let bounds = patch.bounds();
+
+    for (position, &morph_height) in patch.mesh().positions.iter().zip(patch.morph_heights()) {
+        let position = patch.origin() + *position;
+        assert!(bounds.contains(position));
+        assert!(bounds.contains(Vec3::new(position.x, morph_height, position.z)));
+    }
+}
+
+#[test]
+fn cull_stats_count_drawn_and_culled_objects() {
+    let frustum = Frustum::from_matrix(&view_projection(Vec3::zero(), -Vec3::unit_z()));
+    let boxes = [
+        cube(Vec3::new(0.0, 0.0, -10.0), 1.0),
+        cube(Vec3::new(0.0, 0.0, 10.0), 1.0),
+        cube(Vec3::new(0.0, 0.0, -100.0), 5.0),
+        cube(Vec3::new(0.0, 0.0, -200.0), 5.0),
+        cube(Vec3::new(50.0, 0.0, -10.0), 1.0),
+    ];
+
+    let mut stats = CullStats::default();
+    let drawn = boxes.iter().filter(|aabb| stats.count(frustum.intersects(aabb))).count();
+
+    assert_eq!(drawn, 2);
+    assert_eq!(stats, CullStats { drawn: 2, culled: 3 });
+}
@@ -0,0 +1,59 @@
+use procedural_generation::generation::*;
+
+fn perlin_terrain(seed: u32) -> Terrain {
+    Terrain::new(
+        16,
+        16,
+        1.0,
+        PerlinSource::new(NoiseParams {
+            seed,
+            ..Default::default()
+        }),
+    )
+}
+
+#[test]
+fn same_seed_generates_identical_heights() {
+    assert_eq!(perlin_terrain(1234).heightfield(), perlin_terrain(1234).heightfield());
+}
+
+#[test]
+fn different_seeds_generate_different_heights() {
+    assert_ne!(perlin_terrain(1234).heightfield(), perlin_terrain(1235).heightfield());
+}
+
+#[test]
+fn every_noise_kind_generates_finite_heights() {
+    for kind in NoiseKind::ALL.iter() {
+        let heightfield = Heightfield::generate(16, 16, kind.source(NoiseParams::default()).as_ref());
+
+        assert!(heightfield.heights().iter().all(|h| h.is_finite()), "{} produced a non-finite height", kind);
+    }
+}
+
+#[test]
+fn example_recipe_builds() {
+    let recipe = HeightNode::from_file("recipes/continents.json").unwrap();
+    let heightfield = Heightfield::generate(16, 16, recipe.build(7).as_ref());
+
+    assert!(heightfield.heights().iter().all(|h| h.is_finite()));
+    assert_eq!(heightfield, Heightfield::generate(16, 16, recipe.build(7).as_ref()));
+}
+
+#[test]
+fn hydraulic_erosion_is_deterministic() {
+    let erode = || {
+        let mut heightfield = perlin_terrain(99).heightfield();
+        HydraulicErosion::new(HydraulicErosionParams {
+            seed: 5,
+            droplets: 500,
+            ..Default::default()
+        })
+        .apply(&mut heightfield);
+        heightfield
+    };
+
+    let eroded = erode();
+    assert_eq!(eroded, erode());
+    assert_ne!(eroded, perlin_terrain(99).heightfield());
+}
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use procedural_generation::generation::*;
+use vek::vec::{Vec2, Vec3};
+
+fn lod() -> LodTerrain<()> {
+    lod_of(LodParams {
+        patch_size: 8,
+        levels: 4,
+        base_range: MIN_LOD_RANGE,
+    })
+}
+
+fn lod_of(params: LodParams) -> LodTerrain<()> {
+    let terrain = ChunkedTerrain::new(PerlinSource::new(NoiseParams::default()), 8, 1.5).with_noise_scale(128.0);
+
+    LodTerrain::new(terrain, params)
+}
+
+fn cameras() -> [Vec3<f32>; 4] {
+    [
+        Vec3::new(0.0, 10.0, 0.0),
+        Vec3::new(123.4, 40.0, -77.7),
+        Vec3::new(-517.0, 3.0, 250.2),
+        Vec3::new(30.0, 150.0, 30.0),
+    ]
+}
+
+// World-space corners of the area a node covers
+fn bounds(lod: &LodTerrain<()>, node: LodNode) -> (Vec2<f32>, Vec2<f32>) {
+    let extent = lod.patch_extent(node.level);
+    let min = Vec2::new(node.x as f32, node.z as f32) * extent;
+    (min, min + extent)
+}
+
+// Length of the boundary two nodes share, zero if they only meet at a corner or not at all
+fn shared_edge(a: (Vec2<f32>, Vec2<f32>), b: (Vec2<f32>, Vec2<f32>)) -> f32 {
+    let overlap = |a_min: f32, a_max: f32, b_min: f32, b_max: f32| a_max.min(b_max) - a_min.max(b_min);
+    let (x, z) = (overlap(a.0.x, a.1.x, b.0.x, b.1.x), overlap(a.0.y, a.1.y, b.0.y, b.1.y));
+
+    match (x.abs() < 1e-3, z.abs() < 1e-3) {
+        (true, false) if z > 0.0 => z,
+        (false, true) if x > 0.0 => x,
+        _ => 0.0,
+    }
+}
+
+// A vertex of a patch in world space, morphed as the vertex shader does for `camera`
+fn morphed(lod: &LodTerrain<()>, patch: &LodPatch, i: usize, camera: Vec3<f32>) -> Vec3<f32> {
+    let position = patch.origin() + patch.mesh().positions[i];
+    let morph = lod.morph(patch.node().level, position, camera);
+
+    Vec3::new(position.x, position.y + (patch.morph_heights()[i] - position.y) * morph, position.z)
+}
+
+// Height of the morphed patch at a point on its boundary, between the two vertices around it
+fn edge_height(lod: &LodTerrain<()>, patch: &LodPatch, point: Vec2<f32>, camera: Vec3<f32>) -> f32 {
+    let size = lod.params().patch_size as usize;
+    let spacing = lod.patch_extent(patch.node().level) / size as f32;
+    let local = (point - Vec2::new(patch.origin().x, patch.origin().z)) / spacing;
+    let index = |x: usize, z: usize| x * (size + 1) + z;
+
+    let on_edge = |v: f32| v.abs() < 1e-3 || (v - size as f32).abs() < 1e-3;
+    let (fixed, along, along_x) = if on_edge(local.x) {
+        (local.x.round() as usize, local.y, false)
+    } else {
+        assert!(on_edge(local.y), "{} is not on the edge of {:?}", point, patch.node());
+        (local.y.round() as usize, local.x, true)
+    };
+
+    let first = (along.floor() as usize).min(size - 1);
+    let t = along - first as f32;
+    let (a, b) = if along_x {
+        (index(first, fixed), index(first + 1, fixed))
+    } else {
+        (index(fixed, first), index(fixed, first + 1))
+    };
+
+    let (a, b) = (morphed(lod, patch, a, camera).y, morphed(lod, patch, b, camera).y);
+    a + (b - a) * t
+}
+
+#[test]
+fn selected_patches_tile_the_plane_once() {
+    let lod = lod();
+
+    for camera in cameras() {
+        let selected = lod.select(camera);
+        let reach = lod.range(lod.params().levels - 1) * 0.5;
+
+        for x in -20..=20 {
+            for z in -20..=20 {
+                let point = Vec2::new(camera.x, camera.z) + Vec2::new(x as f32, z as f32) * (reach / 20.0) + 0.01;
+                let covering = selected
+                    .iter()
+                    .filter(|&&node| {
+                        let (min, max) = bounds(&lod, node);
+                        point.x >= min.x && point.x < max.x && point.y >= min.y && point.y < max.y
+                    })
+                    .count();
+
+                assert_eq!(covering, 1, "{} is covered by {} patches for a camera at {}", point, covering, camera);
+            }
+        }
+    }
+}
+
+#[test]
+fn patches_get_coarser_with_distance_one_level_at_a_time() {
+    let lod = lod();
+
+    for camera in cameras() {
+        let selected = lod.select(camera);
+
+        for (i, &a) in selected.iter().enumerate() {
+            for &b in &selected[i + 1..] {
+                if shared_edge(bounds(&lod, a), bounds(&lod, b)) > 0.0 {
+                    assert!(a.level.abs_diff(b.level) <= 1, "{:?} and {:?} are neighbours", a, b);
+                }
+            }
+        }
+    }
+
+    // Full detail under the camera, coarse in the distance
+    let camera = cameras()[0];
+    let selected = lod.select(camera);
+    assert!(selected.iter().any(|node| node.level == 0 && bounds(&lod, *node).0 == Vec2::zero()));
+    assert!(selected.iter().any(|node| node.level == lod.params().levels - 1));
+
+    // Climbing drops the detail
+    let high = lod.select(Vec3::new(camera.x, lod.range(1), camera.z));
+    assert!(high.iter().all(|node| node.level >= 1));
+    assert!(high.len() < selected.len());
+}
+
+#[test]
+fn morphed_patches_meet_without_cracks() {
+    let lod = lod();
+
+    for camera in cameras() {
+        let patches: Vec<LodPatch> = lod.select(camera).into_iter().map(|node| lod.build_patch(node)).collect();
+        let size = lod.params().patch_size as usize;
+
+        for (i, patch) in patches.iter().enumerate() {
+            let patch_bounds = bounds(&lod, patch.node());
+
+            for (j, other) in patches.iter().enumerate() {
+                let other_bounds = bounds(&lod, other.node());
+                if i == j || shared_edge(patch_bounds, other_bounds) == 0.0 {
+                    continue;
+                }
+
+                // Every vertex of the patch on the shared edge lies on the other patch's edge
+                for x in 0..=size {
+                    for z in 0..=size {
+                        let vertex = morphed(&lod, patch, x * (size + 1) + z, camera);
+                        let point = Vec2::new(vertex.x, vertex.z);
+                        let inside = |v: f32, min: f32, max: f32| v >= min - 1e-3 && v <= max + 1e-3;
+                        let on_shared_edge = inside(point.x, other_bounds.0.x, other_bounds.1.x)
+                            && inside(point.y, other_bounds.0.y, other_bounds.1.y);
+                        if (x != 0 && x != size && z != 0 && z != size) || !on_shared_edge {
+                            continue;
+                        }
+
+                        let other_height = edge_height(&lod, other, point, camera);
+                        assert!(
+                            (vertex.y - other_height).abs() < 1e-3,
+                            "crack of {} at {} between {:?} and {:?} for a camera at {}",
+                            (vertex.y - other_height).abs(),
+                            point,
+                            patch.node(),
+                            other.node(),
+                            camera
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn updates_only_build_newly_selected_patches() {
+    let mut lod = lod();
+    let mut built: BTreeMap<LodNode, usize> = BTreeMap::new();
+    let mut upload = |patch: LodPatch| -> Result<(), ()> {
+        *built.entry(patch.node()).or_default() += 1;
+        Ok(())
+    };
+
+    let camera = cameras()[1];
+    lod.update(camera, &mut upload).unwrap();
+    lod.update(camera, &mut upload).unwrap();
+    lod.update(camera + Vec3::new(40.0, 0.0, 0.0), &mut upload).unwrap();
+
+    let selected = lod.select(camera + Vec3::new(40.0, 0.0, 0.0));
+    assert_eq!(lod.patch_count(), selected.len());
+    assert_eq!(lod.patches().map(|(node, _)| node).collect::<Vec<_>>(), selected);
+    assert!(built.values().all(|&count| count == 1), "a patch was built twice while still selected");
+    assert!(built.len() > selected.len());
+}
+
+#[test]
+fn params_the_quadtree_can_not_be_built_with_are_invalid() {
+    let params = |patch_size, levels, base_range| LodParams {
+        patch_size,
+        levels,
+        base_range,
+    };
+    let invalid = [
+        params(7, 4, 8.0),
+        params(0, 4, 8.0),
+        params(16, 0, 8.0),
+        params(16, 100, 8.0),
+        params(16, 4, 1.0),
+        params(16, 4, f32::NAN),
+        params(16, 4, f32::INFINITY),
+    ];
+
+    for params in invalid {
+        assert!(params.validate().is_err(), "{:?} was accepted", params);
+    }
+    assert_eq!(LodParams::default().validate(), Ok(()));
+
+    // The widest patch of the most levels allowed still has a cell count that fits in a u32
+    assert_eq!(params(16, 28, 8.0).validate(), Ok(()));
+    assert!(params(16, 29, 8.0).validate().is_err());
+    assert_eq!(lod_of(params(16, 28, 8.0)).patch_extent(27), (16u64 << 27) as f32 * 1.5);
+}
@@ -0,0 +1,300 @@
+use procedural_generation::generation::*;
+use procedural_generation::mesh::*;
+use vek::vec::Vec3;
+
+fn terrain(width: u32, depth: u32) -> Terrain {
+    Terrain::new(width, depth, 1.5, PerlinSource::new(NoiseParams::default()))
+}
+
+// Terrain over the heights `surface` gives at each world-space X and Z
+fn terrain_of(width: u32, depth: u32, spacing: f32, surface: impl Fn(f32, f32) -> f32) -> Terrain {
+    let mut heightfield = Heightfield::new(width, depth);
+    for x in 0..width {
+        for z in 0..depth {
+            heightfield.set(x, z, surface(x as f32 * spacing, z as f32 * spacing));
+        }
+    }
+
+    Terrain::from_heightfield(heightfield, spacing)
+}
+
+// Largest angle in degrees between the mesh normals and `normal` at vertices `inside` accepts
+fn max_normal_error(mesh: &TriangleMesh, normal: impl Fn(Vec3<f32>) -> Vec3<f32>, inside: impl Fn(Vec3<f32>) -> bool) -> f32 {
+    mesh.positions
+        .iter()
+        .zip(&mesh.normals)
+        .filter(|(&position, _)| inside(position))
+        .map(|(&position, computed)| computed.dot(normal(position).normalized()).min(1.0).acos().to_degrees())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn terrain_mesh_covers_every_cell_with_two_triangles() {
+    for &(width, depth) in &[(2, 2), (2, 5), (3, 3), (4, 7), (7, 4), (10, 10)] {
+        let mesh = terrain(width, depth).mesh();
+
+        assert_eq!(mesh.vertex_count(), (width * depth) as usize);
+        assert_eq!(mesh.triangle_count(), (2 * (width - 1) * (depth - 1)) as usize);
+    }
+}
+
+// Checks the triangles of a flat grid: indices in range, every cell covered by exactly two
+// triangles that between them use its four corners, and every triangle facing up
+fn check_grid_topology(builder: &GridMeshBuilder) {
+    let (width, depth) = (builder.width(), builder.depth());
+    let positions = (0..width)
+        .flat_map(|x| (0..depth).map(move |z| Vec3::new(x as f32, 0.0, z as f32)))
+        .collect();
+    let mesh = builder.build(positions, Vec::new());
+    let context = format!("{}x{} {:?}", width, depth, builder.topology());
+
+    for &index in &mesh.indices {
+        let restart = builder.topology() == Topology::TriangleStrip && index == PRIMITIVE_RESTART_INDEX;
+        assert!(restart || (index as usize) < mesh.vertex_count(), "{}: index {} out of range", context, index);
+    }
+
+    let mut cells = vec![Vec::new(); ((width - 1) * (depth - 1)) as usize];
+    for triangle in mesh.triangles() {
+        let [a, b, c] = triangle.map(|i| mesh.positions[i as usize]);
+        assert!((b - a).cross(c - a).y > 0.0, "{}: triangle {:?} faces down", context, triangle);
+
+        let (x, z) = (a.x.min(b.x).min(c.x) as u32, a.z.min(b.z).min(c.z) as u32);
+        for corner in [a, b, c] {
+            assert!(corner.x - x as f32 <= 1.0 && corner.z - z as f32 <= 1.0, "{}: triangle {:?} spans cells", context, triangle);
+        }
+        cells[(x * (depth - 1) + z) as usize].extend(triangle);
+    }
+
+    for (cell, corners) in cells.iter_mut().enumerate() {
+        assert_eq!(corners.len(), 6, "{}: cell {} has {} triangles", context, cell, corners.len() / 3);
+        corners.sort_unstable();
+        corners.dedup();
+        assert_eq!(corners.len(), 4, "{}: cell {} is not fully covered", context, cell);
+    }
+
+    assert_eq!(mesh.triangle_count(), cells.len() * 2, "{}: extra triangles", context);
+}
+
+#[test]
+fn grid_builder_covers_every_cell_once_facing_up() {
+    for width in 2..=12 {
+        for depth in 2..=12 {
+            for topology in [Topology::Triangles, Topology::TriangleStrip] {
+                check_grid_topology(&GridMeshBuilder::new(width, depth).with_topology(topology));
+            }
+        }
+    }
+
+    // Random larger and lopsided grids, from a fixed xorshift sequence
+    let mut state = 0x2545_f491_u32;
+    for _ in 0..20 {
+        let mut next = |limit: u32| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            2 + state % (limit - 1)
+        };
+        let (width, depth) = (next(200), next(200));
+
+        for topology in [Topology::Triangles, Topology::TriangleStrip] {
+            check_grid_topology(&GridMeshBuilder::new(width, depth).with_topology(topology));
+        }
+    }
+}
+
+#[test]
+fn restart_strips_need_fewer_indices_than_a_list() {
+    let list = GridMeshBuilder::new(64, 64).with_topology(Topology::Triangles).indices();
+    let strips = GridMeshBuilder::new(64, 64).with_topology(Topology::TriangleStrip).indices();
+
+    assert_eq!(list.len(), 63 * 63 * 6);
+    assert_eq!(strips.len(), 63 * 64 * 2 + 62);
+    assert_eq!(strips.iter().filter(|&&index| index == PRIMITIVE_RESTART_INDEX).count(), 62);
+}
+
+#[test]
+fn terrain_meshes_agree_across_topologies() {
+    let terrain = terrain(9, 6);
+    let strips = terrain.mesh();
+    let list = terrain.mesh_with_topology(Topology::Triangles);
+
+    assert_eq!(strips.positions, list.positions);
+    for (a, b) in strips.normals.iter().zip(&list.normals) {
+        assert!((a - b).magnitude() < 1e-5, "normals {} and {} differ", a, b);
+    }
+    assert_eq!(strips.triangle_count(), list.triangle_count());
+}
+
+#[test]
+fn vertex_index_finds_the_grid_position() {
+    let terrain = terrain(5, 7);
+    let mesh = terrain.mesh();
+
+    for x in 0..terrain.width() {
+        for z in 0..terrain.depth() {
+            let position = mesh.positions[terrain.vertex_index(x, z)];
+            assert_eq!((position.x, position.z), (x as f32 * 1.5, z as f32 * 1.5));
+        }
+    }
+}
+
+#[test]
+fn planes_get_their_exact_normal_everywhere() {
+    type Surface = fn(f32, f32) -> f32;
+
+    let surfaces: [(Surface, Vec3<f32>); 3] = [
+        (|_, _| 4.0, Vec3::new(0.0, 1.0, 0.0)),
+        (|x, _| 0.5 * x - 3.0, Vec3::new(-0.5, 1.0, 0.0)),
+        (|x, z| 2.0 + 0.3 * x - 1.2 * z, Vec3::new(-0.3, 1.0, 1.2)),
+    ];
+
+    for (surface, normal) in surfaces {
+        for &(width, depth, spacing) in &[(2, 2, 1.0), (2, 5, 2.0), (3, 3, 1.0), (4, 7, 0.5), (9, 5, 3.0), (6, 6, 0.1)] {
+            let mesh = terrain_of(width, depth, spacing, surface).mesh();
+            let error = max_normal_error(&mesh, |_| normal, |_| true);
+
+            assert!(error < 0.05, "{}x{} grid, spacing {}: off by {} degrees", width, depth, spacing, error);
+        }
+    }
+}
+
+#[test]
+fn paraboloid_normals_match_the_analytic_ones() {
+    // y = (x² + z²) / 20 around the centre of the grid, with normal (-x / 10, 1, -z / 10)
+    let errors: Vec<f32> = [(11, 2.0), (21, 1.0), (41, 0.5)]
+        .iter()
+        .map(|&(size, spacing)| {
+            let centre = (size - 1) as f32 * spacing / 2.0;
+            let surface = |x: f32, z: f32| ((x - centre).powi(2) + (z - centre).powi(2)) / 20.0;
+            let mesh = terrain_of(size, size, spacing, surface).mesh();
+
+            // Edge vertices only see the triangles on one side and are off by more
+            let inside = |p: Vec3<f32>| {
+                let margin = centre - 0.5 * spacing;
+                (p.x - centre).abs() < margin && (p.z - centre).abs() < margin
+            };
+            let normal = |p: Vec3<f32>| Vec3::new(-(p.x - centre) / 10.0, 1.0, -(p.z - centre) / 10.0);
+
+            max_normal_error(&mesh, normal, inside)
+        })
+        .collect();
+
+    // Every cell is split along the same diagonal, so the triangles around an interior
+    // vertex are symmetric and the errors of a quadratic surface cancel out
+    for error in errors {
+        assert!(error < 0.1, "off by {} degrees", error);
+    }
+}
+
+#[test]
+fn triangle_list_faces_up() {
+    let mesh = terrain(6, 5).mesh().to_triangle_list();
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| mesh.positions[i as usize]);
+        assert!((b - a).cross(c - a).y > 0.0, "triangle {:?} faces down", triangle);
+    }
+}
+
+#[test]
+fn neighbouring_chunks_share_their_edge_vertices() {
+    let world = ChunkedTerrain::new(PerlinSource::new(NoiseParams::default()), 8, 1.5);
+    let size = world.chunk_size() as usize + 1;
+
+    let chunk = world.build_chunk(ChunkCoord::new(-1, 2));
+    let right = world.build_chunk(ChunkCoord::new(0, 2));
+    let far = world.build_chunk(ChunkCoord::new(-1, 3));
+
+    let world_position = |chunk: &TerrainChunk, i: usize| chunk.origin() + chunk.mesh().positions[i];
+    let same = |a: Vec3<f32>, b: Vec3<f32>| (a - b).magnitude() < 1e-4;
+
+    for i in 0..size {
+        // The last column along X meets the first column of the chunk to the right, the last
+        // row along Z the first row of the chunk beyond
+        let pairs = [((size - 1) * size + i, &right, i), (i * size + size - 1, &far, i * size)];
+
+        for (edge, neighbour, shared) in pairs {
+            let (a, b) = (world_position(&chunk, edge), world_position(neighbour, shared));
+            assert!(same(a, b), "edge vertex {} is at {} in one chunk and {} in the other", i, a, b);
+
+            let (a, b) = (chunk.mesh().normals[edge], neighbour.mesh().normals[shared]);
+            assert!(same(a, b), "edge vertex {} has normal {} in one chunk and {} in the other", i, a, b);
+        }
+    }
+
+    assert_eq!(world.chunk_at(-0.1, 24.0), chunk.coord());
+    assert_eq!(world.chunk_at(0.0, 35.9), right.coord());
+}
+
+#[test]
+fn chunked_terrain_has_the_heights_of_the_fixed_grid() {
+    let terrain = terrain(20, 14);
+    let world = ChunkedTerrain::new(PerlinSource::new(NoiseParams::default()), 8, 1.5).with_noise_scale(20.0);
+    let heightfield = terrain.heightfield();
+
+    for &(x, z) in &[(0, 0), (7, 3), (12, 13), (19, 0), (19, 13)] {
+        assert_eq!(world.height(x as i64, z as i64), heightfield.get(x, z), "height at ({}, {})", x, z);
+    }
+
+    let mesh = terrain.mesh();
+    let chunk = world.build_chunk(ChunkCoord::new(1, 0));
+    for &position in &chunk.mesh().positions {
+        let position = chunk.origin() + position;
+        let (x, z) = ((position.x / 1.5).round() as u32, (position.z / 1.5).round() as u32);
+        let expected = mesh.positions[terrain.vertex_index(x, z)];
+
+        assert!((position - expected).magnitude() < 1e-4, "vertex at {} should be at {}", position, expected);
+    }
+}
+
+#[test]
+fn terrain_chunks_cover_the_whole_mesh() {
+    let terrain = terrain(23, 17);
+    let mesh = terrain.mesh();
+    let chunks = terrain.chunks(8);
+
+    assert_eq!(terrain.chunk_count(8), (3, 2));
+    assert_eq!(chunks.len(), 6);
+    assert_eq!(chunks.iter().map(|chunk| chunk.mesh().triangle_count()).sum::<usize>(), mesh.triangle_count());
+
+    for chunk in &chunks {
+        for (position, normal) in chunk.mesh().positions.iter().zip(&chunk.mesh().normals) {
+            let position = chunk.origin() + position;
+            let (x, z) = ((position.x / 1.5).round() as u32, (position.z / 1.5).round() as u32);
+            let i = terrain.vertex_index(x, z);
+
+            assert!((position - mesh.positions[i]).magnitude() < 1e-4);
+
+            // Along the outer edge of the terrain the chunks see the edge repeated beyond it
+            let outer = x == 0 || z == 0 || x == terrain.width() - 1 || z == terrain.depth() - 1;
+            if !outer {
+                assert!((normal - mesh.normals[i]).magnitude() < 1e-4, "normals {} and {} differ", normal, mesh.normals[i]);
+            }
+        }
+    }
+}
+
+#[test]
+fn one_chunk_rebuilds_the_same_as_all_of_them() {
+    let terrain = terrain(20, 20);
+    let chunks = terrain.chunks(6);
+    let heightfield = terrain.heightfield();
+
+    for chunk in &chunks {
+        assert_eq!(&terrain.build_chunk(&heightfield, chunk.coord(), 6), chunk);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Chunk (-1, 0) is outside the 4x4 chunk grid")]
+fn chunks_left_of_the_grid_are_rejected() {
+    let terrain = terrain(20, 20);
+    terrain.build_chunk(&terrain.heightfield(), ChunkCoord::new(-1, 0), 6);
+}
+
+#[test]
+#[should_panic(expected = "Chunk (0, 4) is outside the 4x4 chunk grid")]
+fn chunks_beyond_the_grid_are_rejected() {
+    let terrain = terrain(20, 20);
+    terrain.build_chunk(&terrain.heightfield(), ChunkCoord::new(0, 4), 6);
+}
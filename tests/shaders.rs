@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use procedural_generation::backend::*;
+use vek::mat::{Mat3, Mat4};
+use vek::vec::{Vec2, Vec3, Vec4};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("procedural_generation_{}_{}", std::process::id(), name));
+    fs::create_dir_all(dir.join("common")).unwrap();
+    dir
+}
+
+fn write(dir: &Path, name: &str, source: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn includes_are_resolved_once_relative_to_the_including_file() {
+    let dir = temp_dir("shader_include");
+    let shader = write(&dir, "a.frag", "#version 330 core\n#include \"common/b.glsl\"\n#include \"common/c.glsl\"\nvoid main() {}\n");
+    write(&dir, "common/b.glsl", "// b\n#include \"c.glsl\"\n");
+    write(&dir, "common/c.glsl", "float c() { return 1.0; }\n");
+
+    let preprocessed = PreprocessedSource::load(&shader, &[]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(preprocessed.source(), "#version 330 core\n// b\nfloat c() { return 1.0; }\nvoid main() {}\n");
+    assert_eq!(preprocessed.files().len(), 3);
+}
+
+#[test]
+fn defines_follow_the_version_line() {
+    let dir = temp_dir("shader_defines");
+    let shader = write(&dir, "a.frag", "// comment\n#version 330 core\nvoid main() {}\n");
+
+    let preprocessed = PreprocessedSource::load(&shader, &[("FOG", "1"), ("SHADOWS", "")]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(preprocessed.source(), "// comment\n#version 330 core\n#define FOG 1\n#define SHADOWS \nvoid main() {}\n");
+    assert_eq!(preprocessed.origin(3), None);
+    assert_eq!(preprocessed.origin(5), Some((shader.as_path(), 3)));
+}
+
+#[test]
+fn compile_log_lines_map_back_to_the_original_files() {
+    let dir = temp_dir("shader_log");
+    let shader = write(&dir, "a.frag", "#version 330 core\n#include \"b.glsl\"\nvoid main() {}\n");
+    let included = write(&dir, "b.glsl", "\n\nfloat b() { return x; }\n");
+
+    let preprocessed = PreprocessedSource::load(&shader, &[("FOG", "1")]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // #version, #define FOG 1, two blank lines, then the broken line of b.glsl
+    assert_eq!(preprocessed.origin(5), Some((included.as_path(), 3)));
+
+    let expected = format!("{}:3", included.display());
+    let mesa = preprocessed.map_log("0:5(27): error: `x' undeclared");
+    assert_eq!(mesa, format!("{}(27): error: `x' undeclared", expected));
+
+    let amd = preprocessed.map_log("ERROR: 0:5: 'x' : undeclared identifier");
+    assert_eq!(amd, format!("ERROR: {}: 'x' : undeclared identifier", expected));
+
+    let nvidia = preprocessed.map_log("0(5) : error C1008: undefined variable \"x\"");
+    assert_eq!(nvidia, format!("{} : error C1008: undefined variable \"x\"", expected));
+
+    let main = preprocessed.map_log("0:6(1): warning");
+    assert_eq!(main, format!("{}:3(1): warning", shader.display()));
+
+    assert_eq!(preprocessed.map_log("0:2(1): error"), "<injected>(1): error");
+    assert_eq!(preprocessed.map_log("no line here"), "no line here");
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let dir = temp_dir("shader_cycle");
+    let shader = write(&dir, "a.frag", "#version 330 core\n#include \"b.glsl\"\n");
+    write(&dir, "b.glsl", "#include \"c.glsl\"\n");
+    write(&dir, "c.glsl", "\n#include \"b.glsl\"\n");
+
+    let result = PreprocessedSource::load(&shader, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    match result {
+        Err(Error::Preprocess { path, line, .. }) => {
+            assert!(path.ends_with("c.glsl"));
+            assert_eq!(line, 2);
+        }
+        other => panic!("expected an include cycle error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_and_malformed_includes_are_errors() {
+    let dir = temp_dir("shader_missing");
+    let missing = write(&dir, "a.frag", "#include \"nowhere.glsl\"\n");
+    let malformed = write(&dir, "b.frag", "#include <nowhere.glsl>\n");
+
+    let missing = PreprocessedSource::load(&missing, &[]);
+    let malformed = PreprocessedSource::load(&malformed, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(matches!(missing, Err(Error::Io { path, .. }) if path.ends_with("nowhere.glsl")));
+    assert!(matches!(malformed, Err(Error::Preprocess { line: 1, .. })));
+}
+
+#[test]
+fn bundled_shaders_preprocess() {
+    for name in ["terrain", "light_mvm", "mvm"] {
+        for stage in ["vert", "frag"] {
+            let path = format!("shaders/{}.{}", name, stage);
+            let preprocessed = PreprocessedSource::load(&path, &[]).unwrap();
+
+            assert!(!preprocessed.source().contains("#include"), "{} has an unresolved #include", path);
+        }
+    }
+}
+
+#[test]
+fn gl_version_strings_pick_a_dialect() {
+    let cases = [
+        ("4.5 (Core Profile) Mesa 23.2.1", GlslVersion::Glsl330),
+        ("3.3.0 NVIDIA 535.54.03", GlslVersion::Glsl330),
+        ("OpenGL ES 3.2 Mesa 23.2.1", GlslVersion::GlslEs300),
+        ("OpenGL ES 3.0 (ANGLE 2.1.0)", GlslVersion::GlslEs300),
+        ("3.1 Mesa 20.0.8", GlslVersion::Glsl120),
+        ("2.1 Mesa 10.1.3", GlslVersion::Glsl120),
+    ];
+
+    for &(version, dialect) in &cases {
+        assert_eq!(GlslVersion::from_gl_version(version), dialect, "{}", version);
+    }
+}
+
+#[test]
+fn gles_translation_only_changes_the_header_and_precision() {
+    let fragment = PreprocessedSource::load("shaders/terrain.frag", &[]).unwrap();
+    let translated = fragment.translate(GlslVersion::GlslEs300, ShaderStage::Fragment);
+
+    let version = translated.source().lines().position(|line| line == "#version 300 es").unwrap();
+    assert_eq!(translated.source().lines().nth(version + 1), Some("precision highp float;"));
+    assert_eq!(translated.source().lines().count(), fragment.source().lines().count() + 1);
+    assert!(translated.source().contains("texture(t_texture, tex_coord)"));
+}
+
+#[test]
+fn gl21_translation_rewrites_qualifiers_and_keeps_attribute_locations() {
+    let vertex = PreprocessedSource::load("shaders/terrain.vert", &[]).unwrap();
+    let translated = vertex.translate(GlslVersion::Glsl120, ShaderStage::Vertex);
+    let source = translated.source();
+
+    assert!(source.starts_with("// Model view matrix vertex shader\n\n#version 120\nmat4 inverse(mat4 m)\n"));
+    assert!(source.contains("\nattribute vec3 a_pos;\n"));
+    assert!(source.contains("\nvarying vec2 tex_coord;\n"));
+    assert!(!source.contains("layout"));
+    // Attributes under #ifdef are bound whether or not they are compiled in, GL ignores the
+    // ones a program does not have
+    assert_eq!(
+        translated.attribute_locations(),
+        &[
+            (0, "a_pos".to_string()),
+            (1, "a_normal".to_string()),
+            (2, "a_tex_coord".to_string()),
+            (3, "a_morph_height".to_string())
+        ]
+    );
+
+    // Lines after the injected polyfill still map to the original file
+    let line = source.lines().position(|line| line == "attribute vec3 a_pos;").unwrap() as u32 + 1;
+    assert_eq!(translated.origin(line), Some((Path::new("shaders/terrain.vert"), 4)));
+
+    let fragment = PreprocessedSource::load("shaders/terrain.frag", &[]).unwrap();
+    let translated = fragment.translate(GlslVersion::Glsl120, ShaderStage::Fragment);
+    let source = translated.source();
+
+    assert!(source.contains("\n#define color gl_FragColor\n"));
+    assert!(source.contains("\nvarying vec3 fragment_pos;\n"));
+    assert!(source.contains("texture2D(t_texture, tex_coord)"));
+    assert!(!source.contains("inverse(mat4 m)"));
+}
+
+#[test]
+fn lod_define_switches_on_morphing() {
+    let vertex = PreprocessedSource::load("shaders/terrain.vert", &[("LOD", "1")]).unwrap();
+    let translated = vertex.translate(GlslVersion::Glsl120, ShaderStage::Vertex);
+    let source = translated.source();
+
+    assert!(source.starts_with("// Model view matrix vertex shader\n\n#version 120\n"));
+    assert!(source.contains("\n#define LOD 1\n"));
+    assert!(source.contains("\nattribute float a_morph_height;\n"));
+    assert!(source.contains("\nvarying float morph;\n"));
+
+    let fragment = PreprocessedSource::load("shaders/terrain.frag", &[("LOD", "1")]).unwrap();
+    let translated = fragment.translate(GlslVersion::Glsl120, ShaderStage::Fragment);
+    assert!(translated.source().contains("\nvarying float morph;\n"));
+    assert!(translated.source().contains("\nuniform bool lod_debug;\n"));
+}
+
+#[test]
+fn uniform_values_only_accept_their_glsl_type() {
+    assert!(1.0f32.accepts(UniformType::Float));
+    assert!(Vec2::<f32>::zero().accepts(UniformType::Vec2));
+    assert!(Vec3::<f32>::zero().accepts(UniformType::Vec3));
+    assert!(Vec4::<f32>::zero().accepts(UniformType::Vec4));
+    assert!(Mat3::<f32>::identity().accepts(UniformType::Mat3));
+    assert!(Mat4::<f32>::identity().accepts(UniformType::Mat4));
+    assert!(true.accepts(UniformType::Bool));
+
+    // Integers set ints, bools and the texture unit of samplers
+    for kind in [UniformType::Int, UniformType::Bool, UniformType::Sampler2D] {
+        assert!(0i32.accepts(kind));
+    }
+
+    assert!(!Vec3::<f32>::zero().accepts(UniformType::Mat4));
+    assert!(!1.0f32.accepts(UniformType::Sampler2D));
+    assert!(!0i32.accepts(UniformType::Float));
+}
+
+#[test]
+fn uniform_arrays_count_their_elements() {
+    let lights = [Vec3::<f32>::zero(); 4];
+
+    assert_eq!(lights.count(), 4);
+    assert_eq!(lights[..2].count(), 2);
+    assert_eq!(1.0f32.count(), 1);
+    assert!(lights.accepts(UniformType::Vec3));
+}
+
+struct Mixed {
+    a: f32,
+    b: Vec3<f32>,
+    c: f32,
+    d: Mat3<f32>,
+    e: Vec2<f32>,
+    f: [f32; 2],
+    g: Vec4<f32>,
+}
+
+impl UniformBlock for Mixed {
+    const NAME: &'static str = "Mixed";
+
+    fn visit<V: BlockVisitor>(&self, visitor: &mut V) {
+        visitor.member("a", &self.a);
+        visitor.member("b", &self.b);
+        visitor.member("c", &self.c);
+        visitor.member("d", &self.d);
+        visitor.member("e", &self.e);
+        visitor.member("f", &self.f);
+        visitor.member("g", &self.g);
+    }
+}
+
+fn offsets(writer: &Std140Writer) -> Vec<(&str, usize)> {
+    writer.offsets().iter().map(|(name, offset)| (name.as_str(), *offset)).collect()
+}
+
+#[test]
+fn std140_aligns_vectors_matrices_and_arrays() {
+    let block = Mixed {
+        a: 1.0,
+        b: Vec3::new(2.0, 3.0, 4.0),
+        c: 5.0,
+        d: Mat3::identity(),
+        e: Vec2::new(6.0, 7.0),
+        f: [8.0, 9.0],
+        g: Vec4::zero(),
+    };
+
+    let writer = Std140Writer::write(&block);
+
+    // A float packs into the end of a vec3, mat3 columns and array elements take 16 bytes
+    assert_eq!(offsets(&writer), [("a", 0), ("b", 16), ("c", 28), ("d", 32), ("e", 80), ("f", 96), ("g", 128)]);
+
+    let bytes = writer.into_bytes();
+    let float = |offset: usize| f32::from_ne_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+
+    assert_eq!(bytes.len(), 144);
+    assert_eq!(float(28), 5.0);
+    assert_eq!([float(32), float(36), float(40), float(44)], [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!([float(48), float(52), float(56)], [0.0, 1.0, 0.0]);
+    assert_eq!([float(96), float(112)], [8.0, 9.0]);
+}
+
+// Offsets the driver reports for the block in shaders/frame.glsl
+#[test]
+fn frame_uniforms_match_the_glsl_block() {
+    let writer = Std140Writer::write(&FrameUniforms::default());
+
+    assert_eq!(
+        offsets(&writer),
+        [
+            ("view", 0),
+            ("projection", 64),
+            ("camera_position", 128),
+            ("time", 140),
+            ("sun_direction", 144),
+            ("fog_density", 156),
+            ("sun_color", 160),
+            ("fog_color", 176),
+        ]
+    );
+    assert_eq!(writer.into_bytes().len(), 192);
+
+    let glsl = fs::read_to_string("shaders/frame.glsl").unwrap();
+    let mut members = glsl.lines().filter_map(|line| line.trim().strip_suffix(';')?.split_whitespace().nth(1));
+    for (name, _) in Std140Writer::write(&FrameUniforms::default()).offsets() {
+        assert_eq!(members.next(), Some(name.as_str()));
+    }
+}
+
+#[test]
+fn gl21_translation_turns_uniform_blocks_into_uniforms() {
+    let vertex = PreprocessedSource::load("shaders/terrain.vert", &[]).unwrap();
+    let translated = vertex.translate(GlslVersion::Glsl120, ShaderStage::Vertex);
+    let source = translated.source();
+
+    assert!(!source.contains("uniform Frame"));
+    assert!(source.contains("\n    uniform vec3 fog_color;\n"));
+
+    // Members keep their place in the included file
+    let line = source.lines().position(|line| line == "    uniform mat4 view;").unwrap() as u32 + 1;
+    assert_eq!(translated.origin(line), Some((Path::new("shaders/frame.glsl"), 5)));
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeSet;
+
+use procedural_generation::backend::{Camera, CameraMovement};
+use procedural_generation::generation::*;
+use vek::vec::Vec3;
+
+const FRAME: f32 = 1.0 / 60.0;
+
+fn world() -> ChunkedTerrain {
+    ChunkedTerrain::new(PerlinSource::new(NoiseParams::default()), 8, 1.0).with_noise_scale(64.0)
+}
+
+fn params(workers: usize) -> StreamingParams {
+    StreamingParams {
+        load_radius: 3.0,
+        unload_radius: 4.0,
+        uploads_per_frame: 2,
+        max_pending: 6,
+        workers,
+    }
+}
+
+// One simulated frame: where the camera was and what the streamer did
+struct Frame {
+    position: Vec3<f32>,
+    uploaded: Vec<ChunkCoord>,
+    resident: BTreeSet<ChunkCoord>,
+}
+
+//
+// Flies a camera along a path of `(movement, frames)` legs, updating the streamer once per
+// frame. Waiting for the workers at the end of each frame makes every run see the same chunks
+// generated, however the threads are scheduled
+//
+fn simulate(workers: usize, path: &[(Option<CameraMovement>, usize)]) -> Vec<Frame> {
+    let mut camera = Camera::new(Vec3::new(4.0, 30.0, 4.0), Vec3::new(0.0, 1.0, 0.0), 0.0, 0.0, true);
+    let mut streamer = ChunkStreamer::new(world(), params(workers));
+    let mut frames = Vec::new();
+
+    for (movement, count) in path {
+        for _ in 0..*count {
+            if let Some(movement) = movement {
+                camera.process_keyboard_inputs(*movement, FRAME);
+            }
+
+            let position = *camera.get_position();
+            let mut uploaded = Vec::new();
+            streamer
+                .update(position, |chunk| -> Result<ChunkCoord, ()> {
+                    uploaded.push(chunk.coord());
+                    Ok(chunk.coord())
+                })
+                .unwrap();
+            streamer.wait_for_generation();
+
+            assert!(streamer.pending_count() <= params(workers).max_pending);
+            frames.push(Frame {
+                position,
+                uploaded,
+                resident: streamer.resident().map(|(coord, _)| coord).collect(),
+            });
+        }
+    }
+
+    frames
+}
+
+// Out along X, a pause, off to the side, and another pause to settle
+fn path() -> Vec<(Option<CameraMovement>, usize)> {
+    vec![
+        (Some(CameraMovement::FORWARD), 90),
+        (None, 40),
+        (Some(CameraMovement::RIGHT), 30),
+        (None, 40),
+    ]
+}
+
+fn in_range(position: Vec3<f32>) -> BTreeSet<ChunkCoord> {
+    ChunkStreamer::<()>::new(world(), params(0)).chunks_in_range(position).into_iter().collect()
+}
+
+fn distance(coord: ChunkCoord, position: Vec3<f32>) -> f32 {
+    let x = coord.x as f32 + 0.5 - position.x / 8.0;
+    let z = coord.z as f32 + 0.5 - position.z / 8.0;
+    (x * x + z * z).sqrt()
+}
+
+#[test]
+fn streaming_is_deterministic_whatever_the_worker_count() {
+    let inline = simulate(0, &path());
+
+    for workers in [1, 3] {
+        let threaded = simulate(workers, &path());
+
+        for (i, (a, b)) in inline.iter().zip(&threaded).enumerate() {
+            assert_eq!(a.uploaded, b.uploaded, "frame {} uploaded different chunks with {} workers", i, workers);
+            assert_eq!(a.resident, b.resident, "frame {} differs with {} workers", i, workers);
+        }
+    }
+}
+
+#[test]
+fn resident_chunks_follow_the_camera() {
+    let frames = simulate(2, &path());
+
+    for (i, frame) in frames.iter().enumerate() {
+        assert!(frame.uploaded.len() <= params(2).uploads_per_frame, "frame {} went over budget", i);
+
+        for &coord in &frame.resident {
+            let distance = distance(coord, frame.position);
+            assert!(distance <= params(2).unload_radius, "frame {} kept {:?}, {} chunks away", i, coord, distance);
+        }
+    }
+
+    // Each pause ends with every chunk in range resident, plus any passed on the way that are
+    // not far enough away to unload yet
+    for end in [129, 199] {
+        let frame = &frames[end];
+        assert!(frame.resident.is_superset(&in_range(frame.position)), "frame {} did not settle", end);
+        assert!(frame.uploaded.is_empty());
+    }
+
+    // The camera travelled several chunks, the first ones it loaded are gone
+    let start = &frames[10].resident;
+    assert!(!start.is_empty());
+    assert!(frames[199].resident.is_disjoint(start));
+    assert!(frames[199].position.x > 8.0 * 2.0 * params(2).unload_radius);
+}
+
+#[test]
+fn nearest_chunks_are_uploaded_first() {
+    let frames = simulate(1, &[(None, 30)]);
+    let position = frames[0].position;
+
+    let order: Vec<ChunkCoord> = frames.iter().flat_map(|frame| frame.uploaded.iter().copied()).collect();
+    for pair in order.windows(2) {
+        assert!(distance(pair[0], position) <= distance(pair[1], position), "{:?} was uploaded before {:?}", pair[0], pair[1]);
+    }
+    assert_eq!(order.len(), in_range(position).len());
+}
+
+#[test]
+fn streamed_chunks_match_chunks_built_directly() {
+    let world = world();
+    let mut streamer = ChunkStreamer::new(world.clone(), params(2));
+    let position = Vec3::new(-20.0, 0.0, 13.0);
+
+    while streamer.resident_count() < in_range(position).len() {
+        streamer
+            .update(position, |chunk| -> Result<(), ()> {
+                assert_eq!(chunk, world.build_chunk(chunk.coord()));
+                Ok(())
+            })
+            .unwrap();
+        streamer.wait_for_generation();
+    }
+}
+
+#[test]
+fn failed_uploads_are_retried() {
+    let mut streamer = ChunkStreamer::new(world(), params(0));
+    let position = Vec3::new(4.0, 0.0, 4.0);
+
+    streamer.update(position, |_| -> Result<(), &str> { Ok(()) }).unwrap();
+    assert_eq!(streamer.update(position, |_| Err("out of memory")), Err("out of memory"));
+
+    for _ in 0..40 {
+        streamer.update(position, |_| -> Result<(), &str> { Ok(()) }).unwrap();
+    }
+    assert_eq!(streamer.resident().map(|(coord, _)| coord).collect::<BTreeSet<_>>(), in_range(position));
+}
+
+#[test]
+fn radii_must_be_finite_and_unload_no_closer_than_load() {
+    let invalid = [
+        (-1.0, 4.0),
+        (f32::NAN, 4.0),
+        (f32::INFINITY, f32::INFINITY),
+        (3.0, 2.0),
+        (3.0, f32::NAN),
+    ];
+
+    for (load_radius, unload_radius) in invalid {
+        let params = StreamingParams {
+            load_radius,
+            unload_radius,
+            ..params(0)
+        };
+
+        assert!(params.validate().is_err(), "radii {} and {} were accepted", load_radius, unload_radius);
+    }
+    assert_eq!(params(0).validate(), Ok(()));
+}
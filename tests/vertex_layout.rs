@@ -0,0 +1,129 @@
+use std::mem;
+
+use procedural_generation::backend::*;
+use procedural_generation::drawables::mesh_vertices;
+use procedural_generation::generation::{NoiseParams, PerlinSource, Terrain};
+use vek::vec::{Vec2, Vec3};
+
+#[derive(Clone, Copy, Debug, VertexLayout)]
+#[repr(C)]
+struct TerrainVertex {
+    position: Vec3<f32>,
+    normal: Vec3<f32>,
+    texture_uv: Vec2<f32>,
+}
+
+#[derive(Clone, Copy, Debug, VertexLayout)]
+#[repr(C)]
+struct PackedVertex {
+    position: [Half; 4],
+    #[vertex(normalized)]
+    color: [u8; 4],
+    #[vertex(location = 7, normalized)]
+    uv: Vec2<u16>,
+    #[vertex(integer)]
+    material: u32,
+    weight: i16,
+    #[vertex(integer)]
+    flags: [i8; 2],
+}
+
+#[test]
+fn derived_layout_matches_the_struct() {
+    assert_eq!(TerrainVertex::stride(), 32);
+    assert_eq!(
+        TerrainVertex::attributes(),
+        vec![
+            VertexAttribute::of::<Vec3<f32>>(0, 0, false, false),
+            VertexAttribute::of::<Vec3<f32>>(1, 12, false, false),
+            VertexAttribute::of::<Vec2<f32>>(2, 24, false, false),
+        ]
+    );
+
+    let attributes = PackedVertex::attributes();
+    let summary: Vec<_> = attributes
+        .iter()
+        .map(|a| (a.location, a.components, a.kind, a.normalized, a.integer, a.offset))
+        .collect();
+
+    assert_eq!(PackedVertex::stride(), 24);
+    assert_eq!(
+        summary,
+        vec![
+            (0, 4, AttributeType::F16, false, false, 0),
+            (1, 4, AttributeType::U8, true, false, 8),
+            (7, 2, AttributeType::U16, true, false, 12),
+            (3, 1, AttributeType::U32, false, true, 16),
+            (4, 1, AttributeType::I16, false, false, 20),
+            (5, 2, AttributeType::I8, false, true, 22),
+        ]
+    );
+}
+
+#[test]
+fn vertices_keep_the_raw_bytes() {
+    let vertex = TerrainVertex {
+        position: Vec3::new(1.0, 2.0, 3.0),
+        normal: Vec3::unit_y(),
+        texture_uv: Vec2::new(0.5, 0.25),
+    };
+    let vertices = Vertices::new(&[vertex; 3]);
+
+    assert_eq!(vertices.len(), 3);
+    assert_eq!(vertices.stride(), mem::size_of::<TerrainVertex>());
+    assert_eq!(vertices.data().len(), 96);
+    assert_eq!(vertices.attributes(), &TerrainVertex::attributes()[..]);
+
+    let float_at = |offset: usize| f32::from_ne_bytes(vertices.data()[offset..offset + 4].try_into().unwrap());
+    assert_eq!(float_at(32 + 8), 3.0);
+    assert_eq!(float_at(64 + 16), 1.0);
+    assert_eq!(float_at(64 + 28), 0.25);
+
+    assert!(Vertices::new::<TerrainVertex>(&[]).is_empty());
+}
+
+#[test]
+fn half_floats_round_to_nearest_even() {
+    let bits = |value: f32| Half::from_f32(value).to_bits();
+
+    assert_eq!(bits(0.0), 0x0000);
+    assert_eq!(bits(-0.0), 0x8000);
+    assert_eq!(bits(1.0), 0x3c00);
+    assert_eq!(bits(-2.0), 0xc000);
+    assert_eq!(bits(0.5), 0x3800);
+    assert_eq!(bits(65504.0), 0x7bff);
+
+    // Halfway between two halves goes to the even one, past halfway rounds up
+    assert_eq!(bits(1.0 + 2f32.powi(-11)), 0x3c00);
+    assert_eq!(bits(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    assert_eq!(bits(1.0 + 2f32.powi(-11) + 2f32.powi(-20)), 0x3c01);
+
+    // Overflow, subnormals and underflow
+    assert_eq!(bits(65520.0), 0x7c00);
+    assert_eq!(bits(f32::INFINITY), 0x7c00);
+    assert_eq!(bits(f32::NEG_INFINITY), 0xfc00);
+    assert_eq!(bits(2f32.powi(-24)), 0x0001);
+    assert_eq!(bits(2f32.powi(-15)), 0x0200);
+    assert_eq!(bits(2f32.powi(-26)), 0x0000);
+    assert!(Half::from_f32(f32::NAN).to_f32().is_nan());
+
+    // Every finite half survives the round trip through f32
+    for bits in (0..=0xffffu16).filter(|bits| bits & 0x7c00 != 0x7c00) {
+        let half = Half::from_bits(bits);
+        assert_eq!(Half::from_f32(half.to_f32()), half, "{:04x}", bits);
+    }
+}
+
+#[test]
+fn mesh_vertices_cover_the_requested_range() {
+    let terrain = Terrain::new(4, 6, 1.0, PerlinSource::new(NoiseParams::default()));
+    let mesh = terrain.mesh();
+
+    let all = mesh_vertices(&mesh, 0..mesh.vertex_count());
+    let column = terrain.vertex_index(2, 0)..terrain.vertex_index(3, 0);
+    let part = mesh_vertices(&mesh, column.clone());
+
+    assert_eq!(part.len(), 6);
+    assert_eq!(part.attributes(), all.attributes());
+    assert_eq!(part.data(), &all.data()[column.start * all.stride()..column.end * all.stride()]);
+}